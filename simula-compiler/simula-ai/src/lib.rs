@@ -1,9 +1,11 @@
-use ndarray::{Array, Array2, Array3};
+use ndarray::{Array, Array2, Array3, ShapeBuilder};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
 
 /// Represents the type of an AI model
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ModelType {
     NeuralNetwork,
     DecisionTree,
@@ -52,12 +54,130 @@ pub struct Parameter {
     pub trainable: bool,
 }
 
+/// A parameter's value. The `F32` variants store single-precision data for
+/// models where halving memory is worth the lost precision.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ParameterValue {
     Scalar(f64),
     Vector(Vec<f64>),
     Matrix(Array2<f64>),
     Tensor(Array3<f64>),
+    VectorF32(Vec<f32>),
+    MatrixF32(Array2<f32>),
+    TensorF32(Array3<f32>),
+}
+
+impl ParameterValue {
+    fn hash_contents<H: Hasher>(&self, state: &mut H) {
+        fn hash_elements<H: Hasher>(state: &mut H, tag: u8, shape: &[usize], bits: impl Iterator<Item = u64>) {
+            state.write_u8(tag);
+            for &dim in shape {
+                state.write_u64(dim as u64);
+            }
+            for element in bits {
+                state.write_u64(element);
+            }
+        }
+
+        match self {
+            ParameterValue::Scalar(v) => hash_elements(state, 0, &[], std::iter::once(v.to_bits())),
+            ParameterValue::Vector(v) => hash_elements(state, 1, &[v.len()], v.iter().map(|x| x.to_bits())),
+            ParameterValue::Matrix(m) => hash_elements(state, 2, m.shape(), m.iter().map(|x| x.to_bits())),
+            ParameterValue::Tensor(t) => hash_elements(state, 3, t.shape(), t.iter().map(|x| x.to_bits())),
+            ParameterValue::VectorF32(v) => {
+                hash_elements(state, 4, &[v.len()], v.iter().map(|x| x.to_bits() as u64))
+            }
+            ParameterValue::MatrixF32(m) => {
+                hash_elements(state, 5, m.shape(), m.iter().map(|x| x.to_bits() as u64))
+            }
+            ParameterValue::TensorF32(t) => {
+                hash_elements(state, 6, t.shape(), t.iter().map(|x| x.to_bits() as u64))
+            }
+        }
+    }
+
+    /// Loads a little- or big-endian `f64` or `f32` array from a NumPy `.npy`
+    /// file, converting `f32` data to `f64`. Rank 0 to 3 arrays become
+    /// `Scalar`, `Vector`, `Matrix` and `Tensor` respectively.
+    pub fn from_npy(path: &str) -> Result<ParameterValue, anyhow::Error> {
+        let npy = npyz::NpyFile::new(std::io::BufReader::new(std::fs::File::open(path)?))?;
+        let shape: Vec<usize> = npy.shape().iter().map(|&dim| dim as usize).collect();
+        let fortran_order = npy.order() == npyz::Order::Fortran;
+
+        let data: Vec<f64> = match npy.dtype() {
+            npyz::DType::Plain(ty) if ty.type_char() == npyz::TypeChar::Float && ty.size_field() == 8 => {
+                npy.into_vec::<f64>()?
+            }
+            npyz::DType::Plain(ty) if ty.type_char() == npyz::TypeChar::Float && ty.size_field() == 4 => {
+                npy.into_vec::<f32>()?.into_iter().map(f64::from).collect()
+            }
+            dtype => anyhow::bail!("unsupported .npy dtype {}, expected f4 or f8", dtype.descr()),
+        };
+
+        let rank = shape.len();
+        match rank {
+            0 => return Ok(ParameterValue::Scalar(data[0])),
+            1 => return Ok(ParameterValue::Vector(data)),
+            2 | 3 => {}
+            _ => anyhow::bail!("unsupported .npy rank {}, expected at most 3", rank),
+        }
+
+        let shape = ndarray::IxDyn(&shape);
+        let array = if fortran_order {
+            Array::from_shape_vec(shape.f(), data)?
+        } else {
+            Array::from_shape_vec(shape, data)?
+        };
+        Ok(if rank == 2 {
+            ParameterValue::Matrix(array.into_dimensionality()?)
+        } else {
+            ParameterValue::Tensor(array.into_dimensionality()?)
+        })
+    }
+
+    /// Sum of the squares of every element, widening `f32` data to `f64`
+    pub fn squared_norm(&self) -> f64 {
+        match self {
+            ParameterValue::Scalar(v) => v * v,
+            ParameterValue::Vector(v) => v.iter().map(|x| x * x).sum(),
+            ParameterValue::Matrix(m) => m.iter().map(|x| x * x).sum(),
+            ParameterValue::Tensor(t) => t.iter().map(|x| x * x).sum(),
+            ParameterValue::VectorF32(v) => v.iter().map(|&x| f64::from(x).powi(2)).sum(),
+            ParameterValue::MatrixF32(m) => m.iter().map(|&x| f64::from(x).powi(2)).sum(),
+            ParameterValue::TensorF32(t) => t.iter().map(|&x| f64::from(x).powi(2)).sum(),
+        }
+    }
+
+    /// Dimensions of the value; empty for a scalar
+    pub fn shape(&self) -> Vec<usize> {
+        match self {
+            ParameterValue::Scalar(_) => Vec::new(),
+            ParameterValue::Vector(v) => vec![v.len()],
+            ParameterValue::Matrix(m) => m.shape().to_vec(),
+            ParameterValue::Tensor(t) => t.shape().to_vec(),
+            ParameterValue::VectorF32(v) => vec![v.len()],
+            ParameterValue::MatrixF32(m) => m.shape().to_vec(),
+            ParameterValue::TensorF32(t) => t.shape().to_vec(),
+        }
+    }
+
+    /// Number of elements; 1 for a scalar
+    pub fn num_elements(&self) -> usize {
+        self.shape().iter().product()
+    }
+
+    /// Bytes occupied by the parameter's elements
+    pub fn memory_bytes(&self) -> usize {
+        match self {
+            ParameterValue::Scalar(_) => std::mem::size_of::<f64>(),
+            ParameterValue::Vector(v) => v.len() * std::mem::size_of::<f64>(),
+            ParameterValue::Matrix(m) => m.len() * std::mem::size_of::<f64>(),
+            ParameterValue::Tensor(t) => t.len() * std::mem::size_of::<f64>(),
+            ParameterValue::VectorF32(v) => v.len() * std::mem::size_of::<f32>(),
+            ParameterValue::MatrixF32(m) => m.len() * std::mem::size_of::<f32>(),
+            ParameterValue::TensorF32(t) => t.len() * std::mem::size_of::<f32>(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -99,6 +219,18 @@ pub enum Metric {
     Custom(String),
 }
 
+impl Metric {
+    /// Name under which the metric is recorded during simulation
+    pub fn name(&self) -> &str {
+        match self {
+            Metric::Accuracy => "accuracy",
+            Metric::Loss => "loss",
+            Metric::Reward => "reward",
+            Metric::Custom(name) => name,
+        }
+    }
+}
+
 impl AIModel {
     pub fn new(model_type: ModelType, name: String) -> Self {
         Self {
@@ -111,6 +243,14 @@ impl AIModel {
         }
     }
 
+    /// Metric names declared by the model's simulation config, in order
+    pub fn declared_metrics(&self) -> Vec<String> {
+        self.simulation_config
+            .as_ref()
+            .map(|config| config.metrics.iter().map(|m| m.name().to_string()).collect())
+            .unwrap_or_default()
+    }
+
     pub fn add_parameter(&mut self, name: String, value: ParameterValue, trainable: bool) {
         self.parameters.insert(
             name,
@@ -132,12 +272,167 @@ impl AIModel {
     pub fn set_simulation_config(&mut self, config: SimulationConfig) {
         self.simulation_config = Some(config);
     }
+
+    /// Checks that a neural network model has an architecture, that the
+    /// architecture has one activation function per layer, and that each
+    /// layer's input shape equals the previous layer's output shape.
+    /// Returns every problem found.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        match &self.architecture {
+            None if self.model_type == ModelType::NeuralNetwork => {
+                problems.push("neural network model has no architecture".to_string());
+            }
+            None => {}
+            Some(architecture) => {
+                if architecture.activation_functions.len() != architecture.layers.len() {
+                    problems.push(format!(
+                        "architecture has {} layers but {} activation functions",
+                        architecture.layers.len(),
+                        architecture.activation_functions.len()
+                    ));
+                }
+                for (i, pair) in architecture.layers.windows(2).enumerate() {
+                    if pair[0].output_shape != pair[1].input_shape {
+                        problems.push(format!(
+                            "layer {} outputs shape {:?} but layer {} expects input shape {:?}",
+                            i,
+                            pair[0].output_shape,
+                            i + 1,
+                            pair[1].input_shape
+                        ));
+                    }
+                }
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Total number of elements across every parameter
+    pub fn num_parameters(&self) -> usize {
+        self.parameters.values().map(|parameter| parameter.value.num_elements()).sum()
+    }
+
+    /// Number of elements across the trainable parameters only
+    pub fn num_trainable_parameters(&self) -> usize {
+        self.parameters
+            .values()
+            .filter(|parameter| parameter.trainable)
+            .map(|parameter| parameter.value.num_elements())
+            .sum()
+    }
+
+    /// Human-readable table of each parameter's name, shape and trainable
+    /// flag, in name order, followed by the parameter counts
+    pub fn summary(&self) -> String {
+        let mut names: Vec<&String> = self.parameters.keys().collect();
+        names.sort();
+        let width = names.iter().map(|name| name.len()).max().unwrap_or(0).max("parameter".len());
+
+        let mut summary = format!("{} ({:?})\n", self.name, self.model_type);
+        summary.push_str(&format!("{:<width$}  {:<16}  trainable\n", "parameter", "shape"));
+        for name in names {
+            let parameter = &self.parameters[name];
+            let shape = format!("{:?}", parameter.value.shape());
+            summary.push_str(&format!("{:<width$}  {:<16}  {}\n", name, shape, parameter.trainable));
+        }
+        summary.push_str(&format!(
+            "total parameters: {} ({} trainable)\n",
+            self.num_parameters(),
+            self.num_trainable_parameters()
+        ));
+        summary
+    }
+
+    /// L2 norm over the elements of every parameter
+    pub fn parameter_norm(&self) -> f64 {
+        self.parameters
+            .values()
+            .map(|parameter| parameter.value.squared_norm())
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Writes the model as JSON. Floats round-trip exactly, but NaN and
+    /// infinite values cannot be represented and are written as `null`, which
+    /// `load_json` rejects; use `save_bincode` for such models.
+    pub fn save_json(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    pub fn load_json(path: &Path) -> Result<AIModel, anyhow::Error> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// Writes the model in the compact bincode format, which preserves every
+    /// float bit pattern
+    pub fn save_bincode(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        bincode::serialize_into(file, self)?;
+        Ok(())
+    }
+
+    pub fn load_bincode(path: &Path) -> Result<AIModel, anyhow::Error> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        Ok(bincode::deserialize_from(file)?)
+    }
+
+    /// Hash of the model type, name, and every parameter's trainable flag and
+    /// contents. Parameters are visited in name order so the result does not
+    /// depend on `HashMap` iteration order, and FNV-1a is used so the value is
+    /// stable across builds and platforms.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv1aHasher::default();
+        self.model_type.hash(&mut hasher);
+        self.name.hash(&mut hasher);
+
+        let mut names: Vec<&String> = self.parameters.keys().collect();
+        names.sort();
+        for name in names {
+            let parameter = &self.parameters[name];
+            name.hash(&mut hasher);
+            parameter.trainable.hash(&mut hasher);
+            parameter.value.hash_contents(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+struct Fnv1aHasher(u64);
+
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1aHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
 }
 
 /// Simulation-specific AI model operations
 pub mod simulation {
     use super::*;
-    use tokio::sync::mpsc;
 
     pub struct SimulationContext {
         pub model: AIModel,
@@ -175,13 +470,16 @@ pub mod simulation {
 pub mod training {
     use super::*;
 
+    /// Training state that can be serialized mid-run and resumed later
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct TrainingContext {
         pub model: AIModel,
+        /// Number of epochs already completed; `train` resumes from here
         pub current_epoch: usize,
         pub training_history: Vec<TrainingMetrics>,
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct TrainingMetrics {
         pub epoch: usize,
         pub loss: f64,
@@ -197,12 +495,29 @@ pub mod training {
             }
         }
 
+        /// Runs the remaining epochs up to the configured total, stopping with
+        /// an error as soon as an epoch records a NaN or infinite loss
         pub async fn train(&mut self) -> Result<(), anyhow::Error> {
-            if let Some(config) = &self.model.training_config {
-                for epoch in 0..config.epochs {
-                    self.current_epoch = epoch;
-                    self.train_epoch()?;
+            let epochs = match &self.model.training_config {
+                Some(config) => config.epochs,
+                None => return Ok(()),
+            };
+            while self.current_epoch < epochs {
+                self.train_epoch()?;
+                if let Some(metrics) = self
+                    .training_history
+                    .last()
+                    .filter(|metrics| metrics.epoch == self.current_epoch)
+                {
+                    if !metrics.loss.is_finite() {
+                        anyhow::bail!(
+                            "training diverged at epoch {}: loss is {}",
+                            metrics.epoch,
+                            metrics.loss
+                        );
+                    }
                 }
+                self.current_epoch += 1;
             }
             Ok(())
         }
@@ -212,4 +527,220 @@ pub mod training {
             Ok(())
         }
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    fn model_with_parameters(reversed: bool) -> AIModel {
+        let mut parameters = vec![
+            ("weights", ParameterValue::Matrix(array![[1.0, 2.0], [3.0, 4.0]])),
+            ("bias", ParameterValue::Vector(vec![0.5, -0.5])),
+            ("scale", ParameterValue::Scalar(1.0)),
+        ];
+        if reversed {
+            parameters.reverse();
+        }
+        let mut model = AIModel::new(ModelType::NeuralNetwork, "hashed".to_string());
+        for (name, value) in parameters {
+            model.add_parameter(name.to_string(), value, true);
+        }
+        model
+    }
+
+    #[test]
+    fn content_hash_ignores_insertion_order_and_tracks_matrix_contents() {
+        let model = model_with_parameters(false);
+        assert_eq!(model.content_hash(), model_with_parameters(true).content_hash());
+
+        let mut changed = model.clone();
+        if let Some(Parameter { value: ParameterValue::Matrix(weights), .. }) = changed.parameters.get_mut("weights") {
+            weights[[1, 1]] = 4.5;
+        }
+        assert_ne!(model.content_hash(), changed.content_hash());
+    }
+
+    fn layer(input: usize, output: usize) -> Layer {
+        Layer {
+            neurons: output,
+            input_shape: vec![input],
+            output_shape: vec![output],
+        }
+    }
+
+    #[test]
+    fn validate_reports_every_architecture_problem() {
+        let mut model = AIModel::new(ModelType::NeuralNetwork, "net".to_string());
+        assert_eq!(model.validate(), Err(vec!["neural network model has no architecture".to_string()]));
+        assert_eq!(AIModel::new(ModelType::DecisionTree, "tree".to_string()).validate(), Ok(()));
+
+        model.set_architecture(NeuralNetworkArchitecture {
+            layers: vec![layer(4, 8), layer(6, 2)],
+            activation_functions: vec![ActivationFunction::ReLU],
+        });
+        assert_eq!(
+            model.validate(),
+            Err(vec![
+                "architecture has 2 layers but 1 activation functions".to_string(),
+                "layer 0 outputs shape [8] but layer 1 expects input shape [6]".to_string(),
+            ])
+        );
+
+        model.set_architecture(NeuralNetworkArchitecture {
+            layers: vec![layer(4, 8), layer(8, 2)],
+            activation_functions: vec![ActivationFunction::ReLU, ActivationFunction::Softmax],
+        });
+        assert_eq!(model.validate(), Ok(()));
+    }
+
+    #[test]
+    fn parameter_counts_and_summary_cover_every_parameter() {
+        let mut model = model_with_parameters(false);
+        model.add_parameter("frozen".to_string(), ParameterValue::TensorF32(Array3::zeros((2, 2, 3))), false);
+        assert_eq!(model.num_parameters(), 19);
+        assert_eq!(model.num_trainable_parameters(), 7);
+        assert_eq!(model.parameters["frozen"].value.memory_bytes(), 48);
+
+        let summary = model.summary();
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines[0], "hashed (NeuralNetwork)");
+        let names: Vec<&str> = lines[2..6].iter().map(|line| line.split_whitespace().next().unwrap()).collect();
+        assert_eq!(names, vec!["bias", "frozen", "scale", "weights"]);
+        assert!(lines[3].contains("[2, 2, 3]") && lines[3].ends_with("false"));
+        assert_eq!(lines[6], "total parameters: 19 (7 trainable)");
+    }
+
+    #[test]
+    fn save_and_load_round_trip_every_parameter_value() {
+        let mut model = AIModel::new(ModelType::Custom("persisted".to_string()), "persisted".to_string());
+        let values = [
+            ParameterValue::Scalar(0.1),
+            ParameterValue::Vector(vec![1.0 / 3.0, -2.5]),
+            ParameterValue::Matrix(array![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]),
+            ParameterValue::Tensor(Array3::from_shape_fn((2, 3, 4), |(i, j, k)| (i * 12 + j * 4 + k) as f64 / 7.0)),
+            ParameterValue::VectorF32(vec![0.1, 0.2]),
+            ParameterValue::MatrixF32(array![[1.5f32], [2.5]]),
+            ParameterValue::TensorF32(Array3::from_elem((1, 2, 2), 0.3f32)),
+        ];
+        for (i, value) in values.into_iter().enumerate() {
+            model.add_parameter(format!("p{}", i), value, i % 2 == 0);
+        }
+
+        let dir = std::env::temp_dir();
+        let json = dir.join(format!("simula-ai-round-trip-{}.json", std::process::id()));
+        let bin = dir.join(format!("simula-ai-round-trip-{}.bin", std::process::id()));
+        model.save_json(&json).unwrap();
+        model.save_bincode(&bin).unwrap();
+        let from_json = AIModel::load_json(&json).unwrap();
+        let from_bincode = AIModel::load_bincode(&bin).unwrap();
+        std::fs::remove_file(&json).unwrap();
+        std::fs::remove_file(&bin).unwrap();
+
+        for loaded in [&from_json, &from_bincode] {
+            assert_eq!(loaded.model_type, model.model_type);
+            assert_eq!(loaded.parameters["p3"].value.shape(), vec![2, 3, 4]);
+            assert_eq!(loaded.content_hash(), model.content_hash());
+        }
+    }
+
+    fn write_npy(name: &str, descr: &str, fortran_order: bool, shape: &str, data: &[u8]) -> std::path::PathBuf {
+        let mut header = format!(
+            "{{'descr': '{}', 'fortran_order': {}, 'shape': {}, }}",
+            descr,
+            if fortran_order { "True" } else { "False" },
+            shape
+        );
+        while (10 + header.len() + 1) % 64 != 0 {
+            header.push(' ');
+        }
+        header.push('\n');
+
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(data);
+        let path = std::env::temp_dir().join(format!("simula-ai-{}-{}.npy", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn npy_arrays_load_with_their_shape_and_element_order() {
+        let f8: Vec<u8> = (1..=6).flat_map(|x| (x as f64).to_le_bytes()).collect();
+        let path = write_npy("f8", "<f8", false, "(2, 3)", &f8);
+        let matrix = ParameterValue::from_npy(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(&matrix, ParameterValue::Matrix(m) if m == array![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]));
+        assert_eq!((matrix.shape(), matrix.num_elements()), (vec![2, 3], 6));
+
+        // Fortran order lists columns first; f32 data widens to f64
+        let f4: Vec<u8> = [1.0f32, 3.0, 2.0, 4.0].iter().flat_map(|x| x.to_le_bytes()).collect();
+        let path = write_npy("f4", "<f4", true, "(2, 2)", &f4);
+        let matrix = ParameterValue::from_npy(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(&matrix, ParameterValue::Matrix(m) if m == array![[1.0, 2.0], [3.0, 4.0]]));
+
+        let path = write_npy("vector", "<f8", false, "(6,)", &f8);
+        let vector = ParameterValue::from_npy(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(&vector, ParameterValue::Vector(v) if v.len() == 6));
+
+        let i4: Vec<u8> = (1..=2i32).flat_map(|x| x.to_le_bytes()).collect();
+        let path = write_npy("i4", "<i4", false, "(2,)", &i4);
+        let error = ParameterValue::from_npy(path.to_str().unwrap()).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(error.to_string().contains("unsupported .npy dtype"), "{}", error);
+
+        let scalar = ParameterValue::Scalar(2.0);
+        assert_eq!((scalar.shape(), scalar.num_elements()), (vec![], 1));
+    }
+
+    fn training_context(epochs: usize) -> training::TrainingContext {
+        let mut model = model_with_parameters(false);
+        model.set_training_config(TrainingConfig {
+            optimizer: Optimizer::SGD,
+            loss_function: LossFunction::MSE,
+            batch_size: 1,
+            epochs,
+            learning_rate: 0.1,
+        });
+        training::TrainingContext::new(model)
+    }
+
+    #[tokio::test]
+    async fn training_resumes_after_the_completed_epochs() {
+        let mut context = training_context(3);
+        context.train().await.unwrap();
+        assert_eq!(context.current_epoch, 3);
+
+        let saved = serde_json::to_string(&context).unwrap();
+        let mut resumed: training::TrainingContext = serde_json::from_str(&saved).unwrap();
+        assert_eq!(resumed.current_epoch, 3);
+
+        // A diverged loss logged against epoch 1 would stop a run that
+        // repeated it, so finishing proves epochs 0-2 are not trained again
+        resumed.training_history.push(training::TrainingMetrics {
+            epoch: 1,
+            loss: f64::NAN,
+            accuracy: None,
+        });
+        resumed.model.training_config.as_mut().unwrap().epochs = 5;
+        resumed.train().await.unwrap();
+        assert_eq!(resumed.current_epoch, 5);
+    }
+
+    #[tokio::test]
+    async fn training_stops_at_the_first_non_finite_loss() {
+        let mut context = training_context(5);
+        context.training_history.push(training::TrainingMetrics {
+            epoch: 1,
+            loss: f64::INFINITY,
+            accuracy: None,
+        });
+        let error = context.train().await.unwrap_err();
+        assert_eq!(error.to_string(), "training diverged at epoch 1: loss is inf");
+        assert_eq!(context.current_epoch, 1);
+    }
+}
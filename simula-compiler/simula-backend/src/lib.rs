@@ -1,8 +1,3 @@
-pub mod codegen;
-pub mod targets;
-pub mod optimization;
-pub mod llvm;
-
 use thiserror::Error;
 
 #[derive(Error, Debug)]
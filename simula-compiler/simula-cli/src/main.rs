@@ -1,4 +1,6 @@
 use clap::{Parser, Subcommand};
+use simula_sim::{Scenario, SimulationEngine};
+use std::path::Path;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -30,11 +32,11 @@ enum Commands {
     
     /// Run the simulator
     Run {
-        /// Input file
+        /// Input file, or a JSON scenario file
         #[clap(required = true)]
         input: String,
         
-        /// Simulation duration
+        /// Simulation duration, overriding a scenario's end time
         #[clap(short, long)]
         duration: Option<String>,
     },
@@ -51,7 +53,8 @@ enum Commands {
     },
 }
 
-fn main() -> anyhow::Result<()> {
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
     
     match cli.command {
@@ -60,8 +63,12 @@ fn main() -> anyhow::Result<()> {
                     inputs, output, target, opt_level);
         }
         Commands::Run { input, duration } => {
-            println!("Running simulation from {} with duration {:?}", 
-                    input, duration);
+            if is_scenario(&input) {
+                run_scenario(&input, duration.as_deref()).await?;
+            } else {
+                println!("Running simulation from {} with duration {:?}", 
+                        input, duration);
+            }
         }
         Commands::Verify { input, properties } => {
             println!("Verifying properties from {} against {}", 
@@ -70,4 +77,24 @@ fn main() -> anyhow::Result<()> {
     }
     
     Ok(())
-} 
\ No newline at end of file
+}
+
+fn is_scenario(input: &str) -> bool {
+    Path::new(input)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+async fn run_scenario(input: &str, duration: Option<&str>) -> anyhow::Result<()> {
+    let scenario = Scenario::from_json(&std::fs::read_to_string(input)?)?;
+    let end_time = match duration {
+        Some(duration) => duration.parse::<f64>()?,
+        None => scenario.end_time,
+    };
+
+    let mut engine = SimulationEngine::from_scenario(&scenario)?;
+    engine.run(end_time).await?;
+
+    println!("Scenario {} finished at time {}", input, end_time);
+    Ok(())
+}
@@ -1,9 +1,3 @@
-pub mod lexer;
-pub mod parser;
-pub mod ast;
-pub mod type_checker;
-pub mod diagnostics;
-
 use thiserror::Error;
 
 #[derive(Error, Debug)]
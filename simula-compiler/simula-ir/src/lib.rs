@@ -1,8 +1,3 @@
-pub mod ir;
-pub mod passes;
-pub mod verification;
-pub mod analysis;
-
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -1,181 +1,3379 @@
-use ndarray::{Array1, Array2, Array3};
+use ndarray::{Array1, Array2, LinalgScalar, ScalarOperand};
+use num_traits::Float;
 use rand::Rng;
-use statrs::distribution::{Normal, Distribution};
-use simula_ai::{AIModel, ParameterValue};
+use rand::distributions::uniform::SampleUniform;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+/// Floating-point element types the ML primitives can be instantiated with.
+/// `f64` is the default everywhere; `f32` halves parameter memory at the cost
+/// of precision.
+pub trait MlFloat:
+    Float + LinalgScalar + ScalarOperand + SampleUniform + Debug + Send + Sync
+{
+}
+
+impl MlFloat for f32 {}
+impl MlFloat for f64 {}
+
+/// A trained model that maps a feature vector to a prediction
+pub trait Predict {
+    fn predict(&self, x: &Array1<f64>) -> f64;
+}
+
+/// Trained predictors of any type, looked up by name at runtime
+#[derive(Default)]
+pub struct PredictorRegistry {
+    predictors: BTreeMap<String, Box<dyn Predict>>,
+}
+
+impl PredictorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `predictor` under `name`, replacing any previous one
+    pub fn register(&mut self, name: &str, predictor: Box<dyn Predict>) {
+        self.predictors.insert(name.to_string(), predictor);
+    }
+
+    pub fn predict(&self, name: &str, x: &Array1<f64>) -> Result<f64, anyhow::Error> {
+        self.predictors
+            .get(name)
+            .map(|predictor| predictor.predict(x))
+            .ok_or_else(|| anyhow::anyhow!("no predictor registered as '{}'", name))
+    }
+
+    /// Registered names in sorted order
+    pub fn names(&self) -> Vec<&str> {
+        self.predictors.keys().map(String::as_str).collect()
+    }
+}
 
 /// Common machine learning algorithms and primitives
 pub mod algorithms {
     use super::*;
 
     /// Linear regression implementation
-    pub struct LinearRegression {
+    pub struct LinearRegression<F: MlFloat = f64> {
+        weights: Array1<F>,
+        bias: F,
+    }
+
+    impl<F: MlFloat> LinearRegression<F> {
+        pub fn new(input_dim: usize) -> Self {
+            Self::from_rng(input_dim, &mut rand::thread_rng())
+        }
+
+        /// Like `new`, with the initial weights drawn from
+        /// `util::seeded_rng(seed)` so seeded training runs are reproducible
+        pub fn with_seed(input_dim: usize, seed: u64) -> Self {
+            Self::from_rng(input_dim, &mut util::seeded_rng(seed))
+        }
+
+        fn from_rng(input_dim: usize, rng: &mut impl Rng) -> Self {
+            Self {
+                weights: Array1::from_shape_fn(input_dim, |_| rng.gen_range(-F::one()..F::one())),
+                bias: rng.gen_range(-F::one()..F::one()),
+            }
+        }
+
+        pub fn predict(&self, x: &Array1<F>) -> F {
+            x.dot(&self.weights) + self.bias
+        }
+
+        /// Full-batch gradient descent for at most `epochs` epochs, stopping
+        /// early once the mean squared error has changed by less than `tol`
+        /// between consecutive epochs `patience` times in a row. A `patience`
+        /// of 0 always runs every epoch. Returns the number of epochs run.
+        pub fn train(
+            &mut self,
+            x: &Array2<F>,
+            y: &Array1<F>,
+            learning_rate: F,
+            epochs: usize,
+            tol: F,
+            patience: usize,
+        ) -> usize {
+            let mut previous: Option<F> = None;
+            let mut stalled = 0;
+            for epoch in 0..epochs {
+                let loss = self.train_epoch(x, y, learning_rate, F::zero());
+                if let Some(previous) = previous {
+                    if (previous - loss).abs() < tol {
+                        stalled += 1;
+                    } else {
+                        stalled = 0;
+                    }
+                }
+                if patience > 0 && stalled >= patience {
+                    return epoch + 1;
+                }
+                previous = Some(loss);
+            }
+            epochs
+        }
+
+        /// Ridge regression: runs every epoch like `train` with an extra
+        /// `l2 * weights` term in the weight gradient, shrinking the weights
+        /// toward zero. The bias is not penalized.
+        pub fn train_regularized(
+            &mut self,
+            x: &Array2<F>,
+            y: &Array1<F>,
+            learning_rate: F,
+            epochs: usize,
+            l2: F,
+        ) {
+            for _ in 0..epochs {
+                self.train_epoch(x, y, learning_rate, l2);
+            }
+        }
+
+        /// Mini-batch gradient descent: each epoch visits the rows in a
+        /// shuffled order and takes one step per `batch_size` rows. Epoch
+        /// shuffles are drawn from `seed`, so runs with the same seed are
+        /// reproducible. A `batch_size` of 0 or at least `x.nrows()` degrades
+        /// to full-batch training.
+        pub fn train_minibatch(
+            &mut self,
+            x: &Array2<F>,
+            y: &Array1<F>,
+            learning_rate: F,
+            epochs: usize,
+            batch_size: usize,
+            seed: u64,
+        ) {
+            let n = x.nrows();
+            if batch_size == 0 || batch_size >= n {
+                for _ in 0..epochs {
+                    self.train_epoch(x, y, learning_rate, F::zero());
+                }
+                return;
+            }
+            let mut rng = util::seeded_rng(seed);
+            let mut rows: Vec<usize> = (0..n).collect();
+            for _ in 0..epochs {
+                util::seeded_shuffle(&mut rows, rng.gen());
+                for batch in rows.chunks(batch_size) {
+                    let x_batch = x.select(ndarray::Axis(0), batch);
+                    let y_batch = y.select(ndarray::Axis(0), batch);
+                    self.train_epoch(&x_batch, &y_batch, learning_rate, F::zero());
+                }
+            }
+        }
+
+        /// Runs every epoch like `train`, returning the mean squared error
+        /// measured at the start of each epoch. Stops with an error at the first epoch whose
+        /// loss is NaN or infinite instead of running the remaining epochs.
+        pub fn train_with_history(
+            &mut self,
+            x: &Array2<F>,
+            y: &Array1<F>,
+            learning_rate: F,
+            epochs: usize,
+        ) -> Result<Vec<F>, anyhow::Error> {
+            let mut history = Vec::with_capacity(epochs);
+            for epoch in 0..epochs {
+                let loss = self.train_epoch(x, y, learning_rate, F::zero());
+                if !loss.is_finite() {
+                    anyhow::bail!("training diverged at epoch {}: loss is {:?}", epoch, loss);
+                }
+                history.push(loss);
+            }
+            Ok(history)
+        }
+
+        /// One gradient step with an L2 penalty on the weights; returns the
+        /// mean squared error before the update. The data gradient is averaged
+        /// over the samples so the step size doesn't grow with the dataset.
+        fn train_epoch(&mut self, x: &Array2<F>, y: &Array1<F>, learning_rate: F, l2: F) -> F {
+            let predictions = x.dot(&self.weights) + self.bias;
+            let errors = y - &predictions;
+            let n = F::from(errors.len().max(1)).unwrap();
+            let loss = errors.mapv(|e| e * e).sum() / n;
+
+            // Step against the gradient of half the MSE, which is
+            // -(x^T errors) / n for the weights and -sum(errors) / n for the bias
+            let weight_gradients = x.t().dot(&errors) / n - &(&self.weights * l2);
+            self.weights = &self.weights + &(weight_gradients * learning_rate);
+            self.bias = self.bias + learning_rate * errors.sum() / n;
+            loss
+        }
+
+        /// Coefficient of determination `1 - SS_res / SS_tot` on `x` and `y`.
+        /// When `y` is constant (`SS_tot == 0`) the score is 0 if the
+        /// predictions are constant too and negative infinity otherwise. NaN
+        /// when there are no samples.
+        pub fn r2_score(&self, x: &Array2<F>, y: &Array1<F>) -> F {
+            if y.is_empty() {
+                return F::nan();
+            }
+            let predictions = x.dot(&self.weights) + self.bias;
+            let mean = y.sum() / F::from(y.len()).unwrap();
+            let ss_res = (y - &predictions).mapv(|e| e * e).sum();
+            let ss_tot = y.mapv(|t| (t - mean) * (t - mean)).sum();
+            if ss_tot == F::zero() {
+                let first = predictions[0];
+                return if predictions.iter().all(|&p| p == first) {
+                    F::zero()
+                } else {
+                    F::neg_infinity()
+                };
+            }
+            F::one() - ss_res / ss_tot
+        }
+
+        /// Bytes occupied by the weights and bias
+        pub fn parameter_bytes(&self) -> usize {
+            (self.weights.len() + 1) * std::mem::size_of::<F>()
+        }
+    }
+
+    impl Predict for LinearRegression<f64> {
+        fn predict(&self, x: &Array1<f64>) -> f64 {
+            LinearRegression::predict(self, x)
+        }
+    }
+
+    /// Linear regression with a vector of targets per sample
+    pub struct MultiOutputLinearRegression {
+        weights: Array2<f64>,
+        bias: Array1<f64>,
+    }
+
+    impl MultiOutputLinearRegression {
+        pub fn new(input_dim: usize, output_dim: usize) -> Self {
+            let mut rng = rand::thread_rng();
+            Self {
+                weights: Array2::from_shape_fn((input_dim, output_dim), |_| rng.gen_range(-1.0..1.0)),
+                bias: Array1::zeros(output_dim),
+            }
+        }
+
+        pub fn predict(&self, x: &Array1<f64>) -> Result<Array1<f64>, anyhow::Error> {
+            if x.len() != self.weights.nrows() {
+                anyhow::bail!("expected {} features, got {}", self.weights.nrows(), x.len());
+            }
+            Ok(x.dot(&self.weights) + &self.bias)
+        }
+
+        pub fn predict_batch(&self, x: &Array2<f64>) -> Result<Array2<f64>, anyhow::Error> {
+            if x.ncols() != self.weights.nrows() {
+                anyhow::bail!("expected {} features, got {}", self.weights.nrows(), x.ncols());
+            }
+            Ok(x.dot(&self.weights) + &self.bias)
+        }
+
+        /// Full-batch gradient descent on the mean squared error, one column
+        /// of `y` per output
+        pub fn train(
+            &mut self,
+            x: &Array2<f64>,
+            y: &Array2<f64>,
+            learning_rate: f64,
+            epochs: usize,
+        ) -> Result<(), anyhow::Error> {
+            if x.nrows() != y.nrows() || y.ncols() != self.weights.ncols() {
+                anyhow::bail!(
+                    "expected {} target columns for {} samples, got a {}x{} target matrix",
+                    self.weights.ncols(),
+                    x.nrows(),
+                    y.nrows(),
+                    y.ncols()
+                );
+            }
+            let scale = learning_rate / x.nrows().max(1) as f64;
+            for _ in 0..epochs {
+                let errors = y - &self.predict_batch(x)?;
+                self.weights = &self.weights + &(x.t().dot(&errors) * scale);
+                self.bias = &self.bias + &(errors.sum_axis(ndarray::Axis(0)) * scale);
+            }
+            Ok(())
+        }
+    }
+
+    /// Binary classifier fit by gradient descent on the binary cross-entropy
+    pub struct LogisticRegression {
         weights: Array1<f64>,
         bias: f64,
+        l2: f64,
     }
 
-    impl LinearRegression {
+    impl LogisticRegression {
+        /// Starts from zero weights; the loss is convex so no random
+        /// initialization is needed
         pub fn new(input_dim: usize) -> Self {
-            let mut rng = rand::thread_rng();
             Self {
-                weights: Array1::from_vec(vec![rng.gen_range(-1.0..1.0); input_dim]),
-                bias: rng.gen_range(-1.0..1.0),
+                weights: Array1::zeros(input_dim),
+                bias: 0.0,
+                l2: 0.0,
             }
         }
 
+        /// Adds `l2 / 2 * |weights|^2` to the training loss. The bias is not
+        /// penalized.
+        pub fn with_l2(mut self, l2: f64) -> Self {
+            self.l2 = l2;
+            self
+        }
+
+        pub fn weights(&self) -> &Array1<f64> {
+            &self.weights
+        }
+
+        pub fn bias(&self) -> f64 {
+            self.bias
+        }
+
+        /// Probability that `x` belongs to the positive class
+        pub fn predict_proba(&self, x: &Array1<f64>) -> f64 {
+            let z = x.dot(&self.weights) + self.bias;
+            1.0 / (1.0 + (-z).exp())
+        }
+
+        /// 1.0 when the positive class probability is at least 0.5, else 0.0
         pub fn predict(&self, x: &Array1<f64>) -> f64 {
-            x.dot(&self.weights) + self.bias
+            if self.predict_proba(x) >= 0.5 {
+                1.0
+            } else {
+                0.0
+            }
         }
 
-        pub fn train(&mut self, x: &Array2<f64>, y: &Array1<f64>, learning_rate: f64, epochs: usize) {
+        /// Full-batch gradient descent on the mean binary cross-entropy
+        /// against 0/1 labels in `y`; returns the loss before the last step
+        pub fn train(
+            &mut self,
+            x: &Array2<f64>,
+            y: &Array1<f64>,
+            learning_rate: f64,
+            epochs: usize,
+        ) -> Result<f64, anyhow::Error> {
+            if x.nrows() != y.len() {
+                anyhow::bail!("x has {} rows but y has {} targets", x.nrows(), y.len());
+            }
+            if x.ncols() != self.weights.len() {
+                anyhow::bail!("expected {} features, got {}", self.weights.len(), x.ncols());
+            }
+            let n = x.nrows().max(1) as f64;
+            let mut loss = f64::NAN;
             for _ in 0..epochs {
-                let predictions = x.dot(&self.weights) + self.bias;
-                let errors = y - &predictions;
-                
-                // Update weights and bias
-                let weight_gradients = x.t().dot(&errors);
-                self.weights = &self.weights + learning_rate * &weight_gradients;
-                self.bias += learning_rate * errors.sum();
+                let probabilities = (x.dot(&self.weights) + self.bias).mapv(|z| 1.0 / (1.0 + (-z).exp()));
+                loss = evaluation::cross_entropy(y, &probabilities)
+                    + 0.5 * self.l2 * self.weights.dot(&self.weights);
+
+                // d(loss)/dz is simply the prediction error for a sigmoid
+                // output under cross-entropy
+                let errors = probabilities - y;
+                let weight_gradients = x.t().dot(&errors) / n + &self.weights * self.l2;
+                self.weights = &self.weights - &(weight_gradients * learning_rate);
+                self.bias -= learning_rate * errors.sum() / n;
             }
+            Ok(loss)
+        }
+    }
+
+    impl Predict for LogisticRegression {
+        fn predict(&self, x: &Array1<f64>) -> f64 {
+            LogisticRegression::predict(self, x)
         }
     }
 
+    /// Impurity a `DecisionTree` minimizes when choosing splits
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum Criterion {
+        /// Regression: leaves predict the mean target
+        #[default]
+        Variance,
+        /// Classification: leaves predict the majority class label
+        Gini,
+        /// Classification: leaves predict the majority class label
+        Entropy,
+    }
+
     /// Decision tree implementation
     pub struct DecisionTree {
         max_depth: usize,
         min_samples_split: usize,
+        criterion: Criterion,
+        categorical: Vec<usize>,
+        /// Features the split search may use, all of them when `None`
+        features: Option<Vec<usize>>,
         root: Option<Box<TreeNode>>,
     }
 
+    /// A tree node. Internal nodes send a sample left when its feature is
+    /// `<= threshold`, or `== threshold` for categorical features.
     struct TreeNode {
         feature_index: usize,
         threshold: f64,
+        categorical: bool,
         left: Option<Box<TreeNode>>,
         right: Option<Box<TreeNode>>,
         value: Option<f64>,
+        /// Weighted impurity removed by this node's split
+        impurity_decrease: f64,
+    }
+
+    impl TreeNode {
+        fn leaf(value: f64) -> Self {
+            Self {
+                feature_index: 0,
+                threshold: 0.0,
+                categorical: false,
+                left: None,
+                right: None,
+                value: Some(value),
+                impurity_decrease: 0.0,
+            }
+        }
+
+        fn goes_left(&self, feature: f64) -> bool {
+            if self.categorical {
+                feature == self.threshold
+            } else {
+                feature <= self.threshold
+            }
+        }
     }
 
     impl DecisionTree {
-        pub fn new(max_depth: usize, min_samples_split: usize) -> Self {
+        pub fn new(max_depth: usize, min_samples_split: usize, criterion: Criterion) -> Self {
             Self {
                 max_depth,
                 min_samples_split,
+                criterion,
+                categorical: Vec::new(),
+                features: None,
                 root: None,
             }
         }
 
+        /// Treats the given feature indices as categorical codes, split by
+        /// equality with a single category rather than by a threshold
+        pub fn with_categorical(mut self, features: &[usize]) -> Self {
+            self.categorical = features.to_vec();
+            self
+        }
+
         pub fn fit(&mut self, x: &Array2<f64>, y: &Array1<f64>) {
             self.root = Some(self.build_tree(x, y, 0));
         }
 
+        /// Walks the fitted tree to a leaf. Panics if called before `fit`.
+        pub fn predict(&self, x: &Array1<f64>) -> f64 {
+            let mut node = self
+                .root
+                .as_deref()
+                .expect("DecisionTree::predict called before fit");
+            while let (Some(left), Some(right)) = (&node.left, &node.right) {
+                node = if node.goes_left(x[node.feature_index]) { left } else { right };
+            }
+            node.value.unwrap_or(f64::NAN)
+        }
+
+        /// Share of the total impurity decrease contributed by splits on each
+        /// feature, summing to 1. Unused features get 0, as do all features of
+        /// a tree that never split. Fails before `fit` or if the tree splits on
+        /// a feature index outside `n_features`.
+        pub fn feature_importances(&self, n_features: usize) -> Result<Array1<f64>, anyhow::Error> {
+            let root = self
+                .root
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("feature importances requested before fit"))?;
+            let mut importances = Array1::zeros(n_features);
+            let mut stack = vec![root];
+            while let Some(node) = stack.pop() {
+                if let (Some(left), Some(right)) = (&node.left, &node.right) {
+                    if node.feature_index >= n_features {
+                        anyhow::bail!(
+                            "tree splits on feature {} but only {} features were given",
+                            node.feature_index,
+                            n_features
+                        );
+                    }
+                    importances[node.feature_index] += node.impurity_decrease;
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+            let total = importances.sum();
+            if total > 0.0 {
+                importances /= total;
+            }
+            Ok(importances)
+        }
+
+        /// Recursive CART construction choosing the split that minimizes the
+        /// children's impurity weighted by their sample counts
         fn build_tree(&self, x: &Array2<f64>, y: &Array1<f64>, depth: usize) -> Box<TreeNode> {
-            // Implementation of decision tree building algorithm
-            unimplemented!()
+            let n = y.len();
+            let leaf_value = match self.criterion {
+                Criterion::Variance => y.mean().unwrap_or(f64::NAN),
+                Criterion::Gini | Criterion::Entropy => majority_class(y.iter().copied()),
+            };
+            if depth >= self.max_depth || n < self.min_samples_split.max(2) {
+                return Box::new(TreeNode::leaf(leaf_value));
+            }
+
+            let parent_error = self.impurity(y.iter().copied());
+            let mut best: Option<(usize, f64, bool, f64)> = None;
+            let features = match &self.features {
+                Some(features) => features.clone(),
+                None => (0..x.ncols()).collect(),
+            };
+            for feature_index in features {
+                let categorical = self.categorical.contains(&feature_index);
+                let column = x.column(feature_index);
+                let mut values: Vec<f64> = column.to_vec();
+                values.sort_by(|a, b| a.total_cmp(b));
+                values.dedup();
+
+                let candidates: Vec<f64> = if categorical {
+                    values
+                } else {
+                    values.windows(2).map(|w| (w[0] + w[1]) / 2.0).collect()
+                };
+                for threshold in candidates {
+                    let goes_left = |v: f64| if categorical { v == threshold } else { v <= threshold };
+                    let left = column.iter().zip(y).filter(|(&v, _)| goes_left(v)).map(|(_, &t)| t);
+                    let right = column.iter().zip(y).filter(|(&v, _)| !goes_left(v)).map(|(_, &t)| t);
+                    let error = self.impurity(left) + self.impurity(right);
+                    let improves = match best {
+                        Some((.., best_error)) => error < best_error,
+                        None => true,
+                    };
+                    if improves {
+                        best = Some((feature_index, threshold, categorical, error));
+                    }
+                }
+            }
+
+            let Some((feature_index, threshold, categorical, error)) = best else {
+                return Box::new(TreeNode::leaf(leaf_value));
+            };
+            if error >= parent_error {
+                return Box::new(TreeNode::leaf(leaf_value));
+            }
+
+            let mut node = TreeNode {
+                feature_index,
+                threshold,
+                categorical,
+                left: None,
+                right: None,
+                value: None,
+                impurity_decrease: parent_error - error,
+            };
+            let (left_rows, right_rows): (Vec<usize>, Vec<usize>) =
+                (0..n).partition(|&row| node.goes_left(x[[row, feature_index]]));
+            for (rows, child) in [(left_rows, &mut node.left), (right_rows, &mut node.right)] {
+                let x_child = x.select(ndarray::Axis(0), &rows);
+                let y_child = y.select(ndarray::Axis(0), &rows);
+                *child = Some(self.build_tree(&x_child, &y_child, depth + 1));
+            }
+            Box::new(node)
         }
-    }
-}
 
-/// Neural network layers and operations
-pub mod neural_network {
-    use super::*;
-    use simula_ai::{ActivationFunction, Layer};
+        /// Impurity of a node under the tree's criterion, scaled by its sample
+        /// count so the sum over children is the weighted impurity
+        fn impurity(&self, values: impl Iterator<Item = f64>) -> f64 {
+            match self.criterion {
+                Criterion::Variance => squared_error(values),
+                Criterion::Gini => {
+                    let counts = class_counts(values);
+                    let n: usize = counts.iter().map(|&(_, count)| count).sum();
+                    let sum_sq: f64 = counts
+                        .iter()
+                        .map(|&(_, count)| (count as f64 / n as f64).powi(2))
+                        .sum();
+                    n as f64 * (1.0 - sum_sq)
+                }
+                Criterion::Entropy => {
+                    let counts = class_counts(values);
+                    let n: usize = counts.iter().map(|&(_, count)| count).sum();
+                    let entropy: f64 = counts
+                        .iter()
+                        .map(|&(_, count)| {
+                            let p = count as f64 / n as f64;
+                            -p * p.log2()
+                        })
+                        .sum();
+                    n as f64 * entropy
+                }
+            }
+        }
+    }
 
-    pub trait Layer {
-        fn forward(&self, input: &Array2<f64>) -> Array2<f64>;
-        fn backward(&self, grad: &Array2<f64>) -> Array2<f64>;
+    impl Predict for DecisionTree {
+        fn predict(&self, x: &Array1<f64>) -> f64 {
+            DecisionTree::predict(self, x)
+        }
     }
 
-    pub struct DenseLayer {
-        weights: Array2<f64>,
-        bias: Array1<f64>,
-        activation: ActivationFunction,
+    /// Bagged ensemble of decision trees, each fit on a bootstrap sample of
+    /// the rows and a random subset of the features
+    pub struct RandomForest {
+        n_trees: usize,
+        max_depth: usize,
+        min_samples_split: usize,
+        max_features: usize,
+        criterion: Criterion,
+        seed: u64,
+        trees: Vec<DecisionTree>,
     }
 
-    impl DenseLayer {
-        pub fn new(input_dim: usize, output_dim: usize, activation: ActivationFunction) -> Self {
-            let mut rng = rand::thread_rng();
+    impl RandomForest {
+        /// `max_features` candidate features per tree, capped at the number
+        /// of columns; 0 means all of them
+        pub fn new(n_trees: usize, max_depth: usize, min_samples_split: usize, max_features: usize) -> Self {
             Self {
-                weights: Array2::from_shape_fn((input_dim, output_dim), |_| rng.gen_range(-1.0..1.0)),
-                bias: Array1::from_vec(vec![0.0; output_dim]),
-                activation,
+                n_trees,
+                max_depth,
+                min_samples_split,
+                max_features,
+                criterion: Criterion::Variance,
+                seed: 0,
+                trees: Vec::new(),
+            }
+        }
+
+        /// Split criterion of every tree. Classification criteria make
+        /// `predict` a majority vote instead of an average.
+        pub fn with_criterion(mut self, criterion: Criterion) -> Self {
+            self.criterion = criterion;
+            self
+        }
+
+        pub fn with_seed(mut self, seed: u64) -> Self {
+            self.seed = seed;
+            self
+        }
+
+        /// Fits each tree on a bootstrap sample of the rows. Fails without
+        /// trees or rows, or when `x` and `y` disagree on the number of rows.
+        pub fn fit(&mut self, x: &Array2<f64>, y: &Array1<f64>) -> Result<(), anyhow::Error> {
+            if self.n_trees == 0 {
+                anyhow::bail!("a random forest needs at least one tree");
+            }
+            if x.nrows() != y.len() {
+                anyhow::bail!("x has {} rows but y has {} targets", x.nrows(), y.len());
+            }
+            if x.nrows() == 0 {
+                anyhow::bail!("cannot fit a random forest on an empty matrix");
+            }
+            let mut rng = util::seeded_rng(self.seed);
+            let n = x.nrows();
+            let max_features = match self.max_features {
+                0 => x.ncols(),
+                max_features => max_features.min(x.ncols()),
+            };
+            self.trees = (0..self.n_trees)
+                .map(|_| {
+                    let rows: Vec<usize> = (0..n).map(|_| rng.gen_range(0..n)).collect();
+                    let mut features: Vec<usize> = (0..x.ncols()).collect();
+                    util::seeded_shuffle(&mut features, rng.gen());
+                    features.truncate(max_features);
+                    features.sort_unstable();
+
+                    let mut tree = DecisionTree::new(self.max_depth, self.min_samples_split, self.criterion);
+                    tree.features = Some(features);
+                    tree.fit(&x.select(ndarray::Axis(0), &rows), &y.select(ndarray::Axis(0), &rows));
+                    tree
+                })
+                .collect();
+            Ok(())
+        }
+
+        /// Mean of the trees' predictions, or their most common label for
+        /// classification criteria. Panics if called before `fit`.
+        pub fn predict(&self, x: &Array1<f64>) -> f64 {
+            assert!(!self.trees.is_empty(), "RandomForest::predict called before fit");
+            let predictions = self.trees.iter().map(|tree| tree.predict(x));
+            match self.criterion {
+                Criterion::Variance => predictions.sum::<f64>() / self.trees.len() as f64,
+                Criterion::Gini | Criterion::Entropy => majority_class(predictions),
             }
         }
     }
 
-    impl Layer for DenseLayer {
-        fn forward(&self, input: &Array2<f64>) -> Array2<f64> {
-            let output = input.dot(&self.weights) + &self.bias;
-            match self.activation {
-                ActivationFunction::ReLU => output.mapv(|x| x.max(0.0)),
-                ActivationFunction::Sigmoid => output.mapv(|x| 1.0 / (1.0 + (-x).exp())),
-                ActivationFunction::Tanh => output.mapv(|x| x.tanh()),
-                _ => output,
+    impl Predict for RandomForest {
+        fn predict(&self, x: &Array1<f64>) -> f64 {
+            RandomForest::predict(self, x)
+        }
+    }
+
+    /// k-means clustering with k-means++ seeding and Lloyd's iteration
+    pub struct KMeans {
+        k: usize,
+        max_iter: usize,
+        seed: u64,
+        centroids: Option<Array2<f64>>,
+        inertia: f64,
+    }
+
+    impl KMeans {
+        pub fn new(k: usize, max_iter: usize) -> Self {
+            Self {
+                k,
+                max_iter,
+                seed: 0,
+                centroids: None,
+                inertia: f64::NAN,
+            }
+        }
+
+        pub fn with_seed(mut self, seed: u64) -> Self {
+            self.seed = seed;
+            self
+        }
+
+        /// Iterates until no row changes cluster or `max_iter` passes have
+        /// run. A cluster left empty is reseeded with the row farthest from
+        /// its current centroid.
+        pub fn fit(&mut self, x: &Array2<f64>) -> Result<(), anyhow::Error> {
+            let n = x.nrows();
+            if self.k == 0 || self.k > n {
+                anyhow::bail!("cannot form {} clusters from {} rows", self.k, n);
+            }
+            let mut centroids = self.initial_centroids(x);
+            let mut labels = vec![usize::MAX; n];
+            for _ in 0..self.max_iter {
+                let assigned: Vec<usize> = x.rows().into_iter().map(|row| nearest(&centroids, row).0).collect();
+                if assigned == labels {
+                    break;
+                }
+                labels = assigned;
+
+                let mut sums = Array2::zeros(centroids.raw_dim());
+                let mut counts = vec![0usize; self.k];
+                for (row, &label) in x.rows().into_iter().zip(&labels) {
+                    let mut sum = sums.row_mut(label);
+                    sum += &row;
+                    counts[label] += 1;
+                }
+                for cluster in 0..self.k {
+                    if counts[cluster] > 0 {
+                        let mean = sums.row(cluster).mapv(|v| v / counts[cluster] as f64);
+                        centroids.row_mut(cluster).assign(&mean);
+                        continue;
+                    }
+                    // Only rows whose cluster would stay non-empty are
+                    // candidates; k <= n guarantees there is one
+                    let distances: Vec<f64> = (0..n)
+                        .map(|i| match counts[labels[i]] {
+                            count if count > 1 => squared_distance(x.row(i), centroids.row(labels[i])),
+                            _ => f64::NEG_INFINITY,
+                        })
+                        .collect();
+                    let farthest = util::argmax(&distances).expect("x has rows");
+                    counts[labels[farthest]] -= 1;
+                    counts[cluster] = 1;
+                    labels[farthest] = cluster;
+                    centroids.row_mut(cluster).assign(&x.row(farthest));
+                }
             }
+
+            self.inertia = x.rows().into_iter().map(|row| nearest(&centroids, row).1).sum();
+            self.centroids = Some(centroids);
+            Ok(())
+        }
+
+        /// Index of the centroid nearest to `x`. Panics if called before `fit`.
+        pub fn predict(&self, x: &Array1<f64>) -> usize {
+            let centroids = self.centroids.as_ref().expect("KMeans::predict called before fit");
+            nearest(centroids, x.view()).0
         }
 
-        fn backward(&self, grad: &Array2<f64>) -> Array2<f64> {
-            // Implementation of backpropagation
-            unimplemented!()
+        /// One centroid per row; `None` before `fit`
+        pub fn centroids(&self) -> Option<&Array2<f64>> {
+            self.centroids.as_ref()
+        }
+
+        /// Sum of squared distances from each training row to its nearest
+        /// centroid; NaN before `fit`
+        pub fn inertia(&self) -> f64 {
+            self.inertia
+        }
+
+        /// k-means++: the first centroid is a uniformly random row, each
+        /// later one a row drawn with probability proportional to its squared
+        /// distance from the nearest centroid chosen so far
+        fn initial_centroids(&self, x: &Array2<f64>) -> Array2<f64> {
+            let mut rng = util::seeded_rng(self.seed);
+            let n = x.nrows();
+            let mut chosen = vec![rng.gen_range(0..n)];
+            let mut distances: Vec<f64> = x
+                .rows()
+                .into_iter()
+                .map(|row| squared_distance(row, x.row(chosen[0])))
+                .collect();
+            while chosen.len() < self.k {
+                let total: f64 = distances.iter().sum();
+                let next = if total > 0.0 {
+                    let mut target = rng.gen::<f64>() * total;
+                    distances
+                        .iter()
+                        .position(|&d| {
+                            target -= d;
+                            d > 0.0 && target <= 0.0
+                        })
+                        .unwrap_or_else(|| distances.iter().rposition(|&d| d > 0.0).unwrap_or(0))
+                } else {
+                    rng.gen_range(0..n)
+                };
+                chosen.push(next);
+                for (distance, row) in distances.iter_mut().zip(x.rows()) {
+                    *distance = distance.min(squared_distance(row, x.row(next)));
+                }
+            }
+            x.select(ndarray::Axis(0), &chosen)
         }
     }
-}
 
-/// Reinforcement learning primitives
-pub mod reinforcement {
-    use super::*;
-    use std::collections::HashMap;
+    /// Principal component analysis by power iteration with deflation on the
+    /// sample covariance matrix
+    pub struct PCA {
+        n_components: usize,
+        projection: Option<Projection>,
+    }
 
-    pub struct QLearning {
-        q_table: HashMap<(usize, usize), f64>,
-        learning_rate: f64,
-        discount_factor: f64,
+    struct Projection {
+        mean: Array1<f64>,
+        /// One unit-length component per row, by decreasing variance
+        components: Array2<f64>,
+        explained_variance_ratio: Array1<f64>,
     }
 
-    impl QLearning {
-        pub fn new(learning_rate: f64, discount_factor: f64) -> Self {
+    impl PCA {
+        const MAX_POWER_ITERATIONS: usize = 1000;
+        const POWER_TOLERANCE: f64 = 1e-12;
+
+        pub fn new(n_components: usize) -> Self {
             Self {
-                q_table: HashMap::new(),
-                learning_rate,
-                discount_factor,
+                n_components,
+                projection: None,
             }
         }
 
-        pub fn update(&mut self, state: usize, action: usize, reward: f64, next_state: usize) {
-            let current_q = self.q_table.get(&(state, action)).copied().unwrap_or(0.0);
-            let next_max_q = self.get_max_q(next_state);
-            
-            let new_q = current_q + self.learning_rate * (reward + self.discount_factor * next_max_q - current_q);
-            self.q_table.insert((state, action), new_q);
+        /// Each component's sign is chosen so its largest-magnitude entry is
+        /// positive, making the result independent of the starting vector
+        pub fn fit(&mut self, x: &Array2<f64>) -> Result<(), anyhow::Error> {
+            let (n, d) = x.dim();
+            if self.n_components == 0 || self.n_components > d {
+                anyhow::bail!("cannot extract {} components from {} features", self.n_components, d);
+            }
+            if n < 2 {
+                anyhow::bail!("PCA needs at least 2 rows, got {}", n);
+            }
+            let mean = x.mean_axis(ndarray::Axis(0)).expect("x has rows");
+            let centered = x - &mean;
+            let mut covariance = centered.t().dot(&centered) / (n - 1) as f64;
+            let total_variance = covariance.diag().sum();
+
+            let mut rng = util::seeded_rng(0);
+            let mut components = Array2::zeros((self.n_components, d));
+            let mut variances = Array1::zeros(self.n_components);
+            for k in 0..self.n_components {
+                let earlier = components.slice(ndarray::s![..k, ..]).to_owned();
+                let mut v: Array1<f64> = Array1::from_shape_fn(d, |_| rng.gen_range(-1.0..1.0));
+                orthonormalize(&mut v, &earlier);
+                for _ in 0..Self::MAX_POWER_ITERATIONS {
+                    let mut next = covariance.dot(&v);
+                    if !orthonormalize(&mut next, &earlier) {
+                        break;
+                    }
+                    let change = (&next - &v).mapv(f64::abs).sum();
+                    v = next;
+                    if change < Self::POWER_TOLERANCE {
+                        break;
+                    }
+                }
+                let magnitudes: Vec<f64> = v.iter().map(|c| c.abs()).collect();
+                if v[util::argmax(&magnitudes).expect("components have entries")] < 0.0 {
+                    v.mapv_inplace(|c| -c);
+                }
+
+                let variance = v.dot(&covariance.dot(&v)).max(0.0);
+                let outer = v
+                    .view()
+                    .insert_axis(ndarray::Axis(1))
+                    .dot(&v.view().insert_axis(ndarray::Axis(0)));
+                covariance.scaled_add(-variance, &outer);
+                components.row_mut(k).assign(&v);
+                variances[k] = variance;
+            }
+
+            let explained_variance_ratio = if total_variance > 0.0 {
+                variances / total_variance
+            } else {
+                Array1::zeros(self.n_components)
+            };
+            self.projection = Some(Projection {
+                mean,
+                components,
+                explained_variance_ratio,
+            });
+            Ok(())
         }
 
-        fn get_max_q(&self, state: usize) -> f64 {
-            // Implementation of max Q-value calculation
-            unimplemented!()
+        /// Coordinates of each row of `x` along the fitted components
+        pub fn transform(&self, x: &Array2<f64>) -> Result<Array2<f64>, anyhow::Error> {
+            let projection = self
+                .projection
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("PCA::transform called before fit"))?;
+            if x.ncols() != projection.mean.len() {
+                anyhow::bail!("PCA was fit on {} columns, got {}", projection.mean.len(), x.ncols());
+            }
+            Ok((x - &projection.mean).dot(&projection.components.t()))
+        }
+
+        pub fn fit_transform(&mut self, x: &Array2<f64>) -> Result<Array2<f64>, anyhow::Error> {
+            self.fit(x)?;
+            self.transform(x)
+        }
+
+        /// One unit-length component per row; `None` before `fit`
+        pub fn components(&self) -> Option<&Array2<f64>> {
+            self.projection.as_ref().map(|p| &p.components)
+        }
+
+        /// Fraction of the total variance captured by each component; `None`
+        /// before `fit`
+        pub fn explained_variance_ratio(&self) -> Option<&Array1<f64>> {
+            self.projection.as_ref().map(|p| &p.explained_variance_ratio)
+        }
+    }
+
+    /// Removes from `v` its projection onto each row of `basis`, whose rows
+    /// must be orthonormal, then scales it to unit length. Leaves `v`
+    /// unchanged and returns false if nothing remains.
+    fn orthonormalize(v: &mut Array1<f64>, basis: &Array2<f64>) -> bool {
+        let mut residual = v.clone();
+        for row in basis.rows() {
+            residual.scaled_add(-residual.dot(&row), &row);
+        }
+        let norm = residual.dot(&residual).sqrt();
+        if norm == 0.0 {
+            return false;
+        }
+        *v = residual / norm;
+        true
+    }
+
+    /// Nearest centroid index and its squared distance
+    fn nearest(centroids: &Array2<f64>, x: ndarray::ArrayView1<f64>) -> (usize, f64) {
+        centroids
+            .rows()
+            .into_iter()
+            .map(|centroid| squared_distance(x, centroid))
+            .enumerate()
+            .fold((0, f64::INFINITY), |best, (i, d)| if d < best.1 { (i, d) } else { best })
+    }
+
+    fn squared_distance(a: ndarray::ArrayView1<f64>, b: ndarray::ArrayView1<f64>) -> f64 {
+        a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+    }
+
+    /// Distinct labels in ascending order with their counts
+    fn class_counts(values: impl Iterator<Item = f64>) -> Vec<(f64, usize)> {
+        let mut values: Vec<f64> = values.collect();
+        values.sort_by(|a, b| a.total_cmp(b));
+        let mut counts: Vec<(f64, usize)> = Vec::new();
+        for value in values {
+            match counts.last_mut() {
+                Some((label, count)) if *label == value => *count += 1,
+                _ => counts.push((value, 1)),
+            }
+        }
+        counts
+    }
+
+    /// Most frequent label, the smallest on ties. NaN when empty.
+    fn majority_class(values: impl Iterator<Item = f64>) -> f64 {
+        let counts = class_counts(values);
+        let frequencies: Vec<f64> = counts.iter().map(|&(_, count)| count as f64).collect();
+        util::argmax(&frequencies).map_or(f64::NAN, |best| counts[best].0)
+    }
+
+    /// Sum of squared deviations from the mean
+    fn squared_error(values: impl Iterator<Item = f64>) -> f64 {
+        let (mut n, mut mean, mut m2) = (0.0, 0.0, 0.0);
+        for value in values {
+            n += 1.0;
+            let delta = value - mean;
+            mean += delta / n;
+            m2 += delta * (value - mean);
         }
+        m2
     }
 }
 
-/// Model evaluation and metrics
-pub mod evaluation {
+/// Neural network layers and operations
+pub mod neural_network {
     use super::*;
+    use simula_ai::{ActivationFunction, LossFunction};
 
-    pub fn accuracy(y_true: &Array1<f64>, y_pred: &Array1<f64>) -> f64 {
-        let correct = y_true.iter().zip(y_pred.iter())
-            .filter(|(true_val, pred_val)| (true_val - pred_val).abs() < 1e-6)
-            .count();
-        correct as f64 / y_true.len() as f64
+    /// A differentiable layer. `forward` caches what `backward` needs, so
+    /// `backward` must follow the `forward` pass it differentiates.
+    pub trait Layer<F: MlFloat = f64> {
+        fn forward(&mut self, input: &Array2<F>) -> Array2<F>;
+        /// Takes the loss gradient with respect to the layer output and
+        /// returns the gradient with respect to its input
+        fn backward(&mut self, grad: &Array2<F>) -> Array2<F>;
+
+        /// Steps the trainable parameters against the gradients stored by the
+        /// last `backward`. Layers without parameters do nothing.
+        fn apply_gradients(&mut self, _learning_rate: F) {}
+
+        /// Switches between training and inference behaviour for layers
+        /// that distinguish them
+        fn set_training(&mut self, _training: bool) {}
     }
 
-    pub fn mean_squared_error(y_true: &Array1<f64>, y_pred: &Array1<f64>) -> f64 {
-        y_true.iter().zip(y_pred.iter())
-            .map(|(true_val, pred_val)| (true_val - pred_val).powi(2))
-            .sum::<f64>() / y_true.len() as f64
+    pub struct DenseLayer<F: MlFloat = f64> {
+        weights: Array2<F>,
+        bias: Array1<F>,
+        activation: ActivationFunction,
+        /// Input and pre-activation output of the last forward pass
+        cache: Option<(Array2<F>, Array2<F>)>,
+        weight_gradients: Array2<F>,
+        bias_gradients: Array1<F>,
     }
 
-    pub fn cross_entropy(y_true: &Array1<f64>, y_pred: &Array1<f64>) -> f64 {
-        y_true.iter().zip(y_pred.iter())
-            .map(|(true_val, pred_val)| {
-                -true_val * pred_val.ln() - (1.0 - true_val) * (1.0 - pred_val).ln()
+    impl<F: MlFloat> DenseLayer<F> {
+        pub fn new(input_dim: usize, output_dim: usize, activation: ActivationFunction) -> Self {
+            let mut rng = rand::thread_rng();
+            Self {
+                weights: Array2::from_shape_fn((input_dim, output_dim), |_| rng.gen_range(-F::one()..F::one())),
+                bias: Array1::from_vec(vec![F::zero(); output_dim]),
+                activation,
+                cache: None,
+                weight_gradients: Array2::zeros((input_dim, output_dim)),
+                bias_gradients: Array1::zeros(output_dim),
+            }
+        }
+
+        /// Builds a layer from an `input_dim x output_dim` weight matrix and a
+        /// bias with one entry per output
+        pub fn from_parameters(
+            weights: Array2<F>,
+            bias: Array1<F>,
+            activation: ActivationFunction,
+        ) -> Result<Self, anyhow::Error> {
+            if bias.len() != weights.ncols() {
+                anyhow::bail!(
+                    "bias has {} entries but the weights have {} outputs",
+                    bias.len(),
+                    weights.ncols()
+                );
+            }
+            Ok(Self {
+                weight_gradients: Array2::zeros(weights.dim()),
+                bias_gradients: Array1::zeros(bias.len()),
+                weights,
+                bias,
+                activation,
+                cache: None,
             })
-            .sum::<f64>() / y_true.len() as f64
+        }
+
+        pub fn weights(&self) -> &Array2<F> {
+            &self.weights
+        }
+
+        pub fn bias(&self) -> &Array1<F> {
+            &self.bias
+        }
+
+        /// Loss gradient with respect to the weights from the last `backward`
+        pub fn weight_gradients(&self) -> &Array2<F> {
+            &self.weight_gradients
+        }
+
+        /// Loss gradient with respect to the bias from the last `backward`
+        pub fn bias_gradients(&self) -> &Array1<F> {
+            &self.bias_gradients
+        }
+
+        /// Bytes occupied by the weights and bias
+        pub fn parameter_bytes(&self) -> usize {
+            (self.weights.len() + self.bias.len()) * std::mem::size_of::<F>()
+        }
+
+        fn activate(&self, z: &Array2<F>) -> Array2<F> {
+            match self.activation {
+                ActivationFunction::ReLU => z.mapv(|x| x.max(F::zero())),
+                ActivationFunction::Sigmoid => z.mapv(sigmoid),
+                ActivationFunction::Tanh => z.mapv(|x| x.tanh()),
+                ActivationFunction::Softmax => softmax_rows(z),
+                ActivationFunction::Custom(_) => z.clone(),
+            }
+        }
+
+        /// Gradient with respect to the pre-activation `z` given the gradient
+        /// with respect to the activation output
+        fn activation_backward(&self, z: &Array2<F>, grad: &Array2<F>) -> Array2<F> {
+            match self.activation {
+                ActivationFunction::ReLU => grad * &z.mapv(|x| if x > F::zero() { F::one() } else { F::zero() }),
+                ActivationFunction::Sigmoid => grad * &z.mapv(|x| {
+                    let s = sigmoid(x);
+                    s * (F::one() - s)
+                }),
+                ActivationFunction::Tanh => grad * &z.mapv(|x| F::one() - x.tanh() * x.tanh()),
+                // Row-wise softmax Jacobian: dz = s * (g - sum(g * s))
+                ActivationFunction::Softmax => {
+                    let s = softmax_rows(z);
+                    let dots = (grad * &s).sum_axis(ndarray::Axis(1)).insert_axis(ndarray::Axis(1));
+                    &s * &(grad - &dots)
+                }
+                ActivationFunction::Custom(_) => grad.clone(),
+            }
+        }
+    }
+
+    impl<F: MlFloat> Layer<F> for DenseLayer<F> {
+        fn forward(&mut self, input: &Array2<F>) -> Array2<F> {
+            let z = input.dot(&self.weights) + &self.bias;
+            let output = self.activate(&z);
+            self.cache = Some((input.clone(), z));
+            output
+        }
+
+        /// Stores the weight and bias gradients and returns the input gradient.
+        /// Panics if called before `forward`.
+        fn backward(&mut self, grad: &Array2<F>) -> Array2<F> {
+            let (input, z) = self
+                .cache
+                .as_ref()
+                .expect("DenseLayer::backward called before forward");
+            let delta = self.activation_backward(z, grad);
+            self.weight_gradients = input.t().dot(&delta);
+            self.bias_gradients = delta.sum_axis(ndarray::Axis(0));
+            delta.dot(&self.weights.t())
+        }
+
+        fn apply_gradients(&mut self, learning_rate: F) {
+            self.weights.scaled_add(-learning_rate, &self.weight_gradients);
+            self.bias.scaled_add(-learning_rate, &self.bias_gradients);
+        }
+    }
+
+    /// Inverted dropout: in training mode each activation is zeroed with
+    /// probability `rate` and survivors are scaled by `1 / (1 - rate)`, so
+    /// the expected activation is unchanged. In inference mode it is the
+    /// identity.
+    pub struct DropoutLayer<F: MlFloat = f64> {
+        rate: f64,
+        training: bool,
+        rng: ChaCha8Rng,
+        /// Scale applied to each activation in the last training forward pass
+        mask: Option<Array2<F>>,
+    }
+
+    impl<F: MlFloat> DropoutLayer<F> {
+        /// A layer in training mode whose masks are drawn from
+        /// `util::seeded_rng(seed)`
+        pub fn new(rate: f64, seed: u64) -> Result<Self, anyhow::Error> {
+            if !(0.0..1.0).contains(&rate) {
+                anyhow::bail!("dropout rate must be in [0, 1), got {}", rate);
+            }
+            Ok(Self {
+                rate,
+                training: true,
+                rng: util::seeded_rng(seed),
+                mask: None,
+            })
+        }
+
+        pub fn rate(&self) -> f64 {
+            self.rate
+        }
+
+        pub fn is_training(&self) -> bool {
+            self.training
+        }
+    }
+
+    impl<F: MlFloat> Layer<F> for DropoutLayer<F> {
+        fn forward(&mut self, input: &Array2<F>) -> Array2<F> {
+            if !self.training {
+                self.mask = None;
+                return input.clone();
+            }
+            let scale = F::from(1.0 / (1.0 - self.rate)).unwrap();
+            let rate = self.rate;
+            let rng = &mut self.rng;
+            let mask = Array2::from_shape_fn(input.dim(), |_| {
+                if rng.gen::<f64>() < rate { F::zero() } else { scale }
+            });
+            let output = input * &mask;
+            self.mask = Some(mask);
+            output
+        }
+
+        /// Routes the gradient through the activations kept in the last
+        /// forward pass, or passes it unchanged in inference mode
+        fn backward(&mut self, grad: &Array2<F>) -> Array2<F> {
+            match &self.mask {
+                Some(mask) => grad * mask,
+                None => grad.clone(),
+            }
+        }
+
+        fn set_training(&mut self, training: bool) {
+            self.training = training;
+        }
+    }
+
+    /// Batch normalization: each feature is standardized with the batch mean
+    /// and (population) variance in training mode, or with running averages
+    /// of them in inference mode, then scaled by `gamma` and shifted by
+    /// `beta`
+    pub struct BatchNorm<F: MlFloat = f64> {
+        gamma: Array1<F>,
+        beta: Array1<F>,
+        gamma_gradients: Array1<F>,
+        beta_gradients: Array1<F>,
+        running_mean: Array1<F>,
+        running_var: Array1<F>,
+        momentum: F,
+        epsilon: F,
+        training: bool,
+        /// Normalized input and per-feature `1 / sqrt(var + epsilon)` of the
+        /// last forward pass
+        cache: Option<(Array2<F>, Array1<F>)>,
+    }
+
+    impl<F: MlFloat> BatchNorm<F> {
+        /// A layer in training mode with `gamma = 1`, `beta = 0`,
+        /// `momentum = 0.1` and `epsilon = 1e-5`
+        pub fn new(num_features: usize) -> Self {
+            Self {
+                gamma: Array1::ones(num_features),
+                beta: Array1::zeros(num_features),
+                gamma_gradients: Array1::zeros(num_features),
+                beta_gradients: Array1::zeros(num_features),
+                running_mean: Array1::zeros(num_features),
+                running_var: Array1::ones(num_features),
+                momentum: F::from(0.1).unwrap(),
+                epsilon: F::from(1e-5).unwrap(),
+                training: true,
+                cache: None,
+            }
+        }
+
+        /// Weight of each new batch in the running statistics
+        pub fn with_momentum(mut self, momentum: F) -> Self {
+            self.momentum = momentum;
+            self
+        }
+
+        pub fn with_epsilon(mut self, epsilon: F) -> Self {
+            self.epsilon = epsilon;
+            self
+        }
+
+        pub fn gamma(&self) -> &Array1<F> {
+            &self.gamma
+        }
+
+        pub fn beta(&self) -> &Array1<F> {
+            &self.beta
+        }
+
+        /// Loss gradient with respect to `gamma` from the last `backward`
+        pub fn gamma_gradients(&self) -> &Array1<F> {
+            &self.gamma_gradients
+        }
+
+        /// Loss gradient with respect to `beta` from the last `backward`
+        pub fn beta_gradients(&self) -> &Array1<F> {
+            &self.beta_gradients
+        }
+
+        pub fn running_mean(&self) -> &Array1<F> {
+            &self.running_mean
+        }
+
+        pub fn running_var(&self) -> &Array1<F> {
+            &self.running_var
+        }
+
+        pub fn is_training(&self) -> bool {
+            self.training
+        }
+    }
+
+    impl<F: MlFloat> Layer<F> for BatchNorm<F> {
+        /// Panics on an empty batch in training mode
+        fn forward(&mut self, input: &Array2<F>) -> Array2<F> {
+            let (mean, var) = if self.training {
+                assert!(input.nrows() > 0, "BatchNorm::forward called with an empty batch");
+                let n = F::from(input.nrows()).unwrap();
+                let mean = input.sum_axis(ndarray::Axis(0)) / n;
+                let var = (input - &mean).mapv(|d| d * d).sum_axis(ndarray::Axis(0)) / n;
+                let keep = F::one() - self.momentum;
+                self.running_mean = &self.running_mean * keep + &(&mean * self.momentum);
+                self.running_var = &self.running_var * keep + &(&var * self.momentum);
+                (mean, var)
+            } else {
+                (self.running_mean.clone(), self.running_var.clone())
+            };
+            let epsilon = self.epsilon;
+            let inv_std = var.mapv(|v| F::one() / (v + epsilon).sqrt());
+            let normalized = (input - &mean) * &inv_std;
+            let output = &normalized * &self.gamma + &self.beta;
+            self.cache = Some((normalized, inv_std));
+            output
+        }
+
+        /// Stores the `gamma` and `beta` gradients and returns the input
+        /// gradient. In training mode this accounts for the batch statistics
+        /// depending on every row. Panics if called before `forward`.
+        fn backward(&mut self, grad: &Array2<F>) -> Array2<F> {
+            let (normalized, inv_std) = self
+                .cache
+                .as_ref()
+                .expect("BatchNorm::backward called before forward");
+            self.gamma_gradients = (grad * normalized).sum_axis(ndarray::Axis(0));
+            self.beta_gradients = grad.sum_axis(ndarray::Axis(0));
+
+            let grad_normalized = grad * &self.gamma;
+            if !self.training {
+                return grad_normalized * inv_std;
+            }
+            // dx = inv_std / n * (n * dx_hat - sum(dx_hat) - x_hat * sum(dx_hat * x_hat))
+            let n = F::from(grad.nrows()).unwrap();
+            let sum = grad_normalized.sum_axis(ndarray::Axis(0));
+            let dot = (&grad_normalized * normalized).sum_axis(ndarray::Axis(0));
+            ((grad_normalized * n - &sum) - &(normalized * &dot)) * &(inv_std / n)
+        }
+
+        fn apply_gradients(&mut self, learning_rate: F) {
+            self.gamma.scaled_add(-learning_rate, &self.gamma_gradients);
+            self.beta.scaled_add(-learning_rate, &self.beta_gradients);
+        }
+
+        fn set_training(&mut self, training: bool) {
+            self.training = training;
+        }
+    }
+
+    /// A stack of layers applied in order, trained end to end by
+    /// backpropagation
+    pub struct Sequential<F: MlFloat = f64> {
+        layers: Vec<Box<dyn Layer<F>>>,
+        /// Mode last set through `set_training`
+        training: bool,
+    }
+
+    impl<F: MlFloat> Default for Sequential<F> {
+        fn default() -> Self {
+            Self {
+                layers: Vec::new(),
+                training: true,
+            }
+        }
+    }
+
+    impl<F: MlFloat> Sequential<F> {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Appends `layer` after the existing ones
+        pub fn add(&mut self, layer: impl Layer<F> + 'static) -> &mut Self {
+            self.layers.push(Box::new(layer));
+            self
+        }
+
+        pub fn len(&self) -> usize {
+            self.layers.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.layers.is_empty()
+        }
+
+        pub fn forward(&mut self, input: &Array2<F>) -> Array2<F> {
+            self.layers
+                .iter_mut()
+                .fold(input.clone(), |activations, layer| layer.forward(&activations))
+        }
+
+        /// Propagates the loss gradient with respect to the output back
+        /// through every layer, returning the gradient with respect to the
+        /// input. Must follow the `forward` pass it differentiates.
+        pub fn backward(&mut self, grad: &Array2<F>) -> Array2<F> {
+            self.layers
+                .iter_mut()
+                .rev()
+                .fold(grad.clone(), |grad, layer| layer.backward(&grad))
+        }
+
+        /// Forward pass with every layer in inference mode. Afterwards the
+        /// layers are returned to the mode last set through `set_training`,
+        /// training by default.
+        pub fn predict(&mut self, input: &Array2<F>) -> Array2<F> {
+            let training = self.training;
+            self.set_training(false);
+            let output = self.forward(input);
+            self.set_training(training);
+            output
+        }
+
+        pub fn set_training(&mut self, training: bool) {
+            self.training = training;
+            for layer in &mut self.layers {
+                layer.set_training(training);
+            }
+        }
+
+        /// One gradient descent step on the batch `x` with targets `y`,
+        /// returning the loss before the update. `MSE` averages over every
+        /// output; `CrossEntropy` is binary for a single output column and
+        /// categorical (over one-hot rows) otherwise, averaged over rows.
+        pub fn train_step(
+            &mut self,
+            x: &Array2<F>,
+            y: &Array2<F>,
+            loss: &LossFunction,
+            learning_rate: F,
+        ) -> Result<F, anyhow::Error> {
+            let output = self.forward(x);
+            if output.dim() != y.dim() {
+                anyhow::bail!("network output is {:?} but targets are {:?}", output.dim(), y.dim());
+            }
+            let (value, grad) = loss_and_gradient(&output, y, loss)?;
+            self.backward(&grad);
+            for layer in &mut self.layers {
+                layer.apply_gradients(learning_rate);
+            }
+            Ok(value)
+        }
+    }
+
+    /// Loss of `output` against `y` and its gradient with respect to `output`
+    fn loss_and_gradient<F: MlFloat>(
+        output: &Array2<F>,
+        y: &Array2<F>,
+        loss: &LossFunction,
+    ) -> Result<(F, Array2<F>), anyhow::Error> {
+        let rows = F::from(output.nrows().max(1)).unwrap();
+        match loss {
+            LossFunction::MSE => {
+                let n = F::from(output.len().max(1)).unwrap();
+                let errors = output - y;
+                let value = errors.mapv(|e| e * e).sum() / n;
+                Ok((value, errors * (F::from(2.0).unwrap() / n)))
+            }
+            LossFunction::CrossEntropy => {
+                let eps = F::from(1e-15).unwrap();
+                let p = output.mapv(|p| p.max(eps).min(F::one() - eps));
+                if output.ncols() == 1 {
+                    let value = ndarray::Zip::from(&p)
+                        .and(y)
+                        .fold(F::zero(), |acc, &p, &t| acc - t * p.ln() - (F::one() - t) * (F::one() - p).ln());
+                    let grad = ndarray::Zip::from(&p)
+                        .and(y)
+                        .map_collect(|&p, &t| (p - t) / (p * (F::one() - p)) / rows);
+                    Ok((value / rows, grad))
+                } else {
+                    let value = ndarray::Zip::from(&p).and(y).fold(F::zero(), |acc, &p, &t| acc - t * p.ln());
+                    let grad = ndarray::Zip::from(&p).and(y).map_collect(|&p, &t| -t / p / rows);
+                    Ok((value / rows, grad))
+                }
+            }
+            LossFunction::Custom(name) => anyhow::bail!("unsupported loss function '{}'", name),
+        }
+    }
+
+    fn sigmoid<F: MlFloat>(x: F) -> F {
+        F::one() / (F::one() + (-x).exp())
+    }
+
+    /// Softmax of each row, shifted by the row maximum so large logits don't
+    /// overflow
+    fn softmax_rows<F: MlFloat>(z: &Array2<F>) -> Array2<F> {
+        let mut output = z.clone();
+        for mut row in output.rows_mut() {
+            let max = row.fold(F::neg_infinity(), |max, &x| max.max(x));
+            row.mapv_inplace(|x| (x - max).exp());
+            let sum = row.sum();
+            row.mapv_inplace(|x| x / sum);
+        }
+        output
+    }
+}
+
+/// Gradient-based parameter update rules
+pub mod optimizers {
+    use super::*;
+
+    /// Updates parameters in place from their gradients. Stateful optimizers
+    /// track one entry per parameter, so an instance must always be stepped
+    /// with parameters of the same length.
+    pub trait Optimizer {
+        fn step(&mut self, params: &mut Array1<f64>, grads: &Array1<f64>);
+    }
+
+    /// Optimizer named by a model's training configuration with its default
+    /// hyperparameters. Custom optimizers have no implementation here.
+    pub fn from_config(
+        optimizer: &simula_ai::Optimizer,
+        learning_rate: f64,
+    ) -> Result<Box<dyn Optimizer>, anyhow::Error> {
+        Ok(match optimizer {
+            simula_ai::Optimizer::SGD => Box::new(Sgd::new(learning_rate)),
+            simula_ai::Optimizer::Adam => Box::new(Adam::new(learning_rate)),
+            simula_ai::Optimizer::RMSprop => Box::new(RMSprop::new(learning_rate)),
+            simula_ai::Optimizer::Custom(name) => anyhow::bail!("unsupported optimizer '{}'", name),
+        })
+    }
+
+    /// Plain gradient descent: `params -= learning_rate * grads`
+    #[derive(Debug, Clone)]
+    pub struct Sgd {
+        learning_rate: f64,
+    }
+
+    impl Sgd {
+        pub fn new(learning_rate: f64) -> Self {
+            Self { learning_rate }
+        }
+    }
+
+    impl Optimizer for Sgd {
+        fn step(&mut self, params: &mut Array1<f64>, grads: &Array1<f64>) {
+            params.scaled_add(-self.learning_rate, grads);
+        }
+    }
+
+    /// Adam: per-parameter steps scaled by bias-corrected running averages
+    /// of the gradient and its square
+    #[derive(Debug, Clone)]
+    pub struct Adam {
+        learning_rate: f64,
+        beta1: f64,
+        beta2: f64,
+        epsilon: f64,
+        t: i32,
+        /// First and second moment estimates
+        moments: Option<(Array1<f64>, Array1<f64>)>,
+    }
+
+    impl Adam {
+        /// `beta1 = 0.9`, `beta2 = 0.999`, `epsilon = 1e-8`
+        pub fn new(learning_rate: f64) -> Self {
+            Self {
+                learning_rate,
+                beta1: 0.9,
+                beta2: 0.999,
+                epsilon: 1e-8,
+                t: 0,
+                moments: None,
+            }
+        }
+
+        pub fn with_betas(mut self, beta1: f64, beta2: f64) -> Self {
+            self.beta1 = beta1;
+            self.beta2 = beta2;
+            self
+        }
+
+        pub fn with_epsilon(mut self, epsilon: f64) -> Self {
+            self.epsilon = epsilon;
+            self
+        }
+    }
+
+    impl Optimizer for Adam {
+        fn step(&mut self, params: &mut Array1<f64>, grads: &Array1<f64>) {
+            let (m, v) = self
+                .moments
+                .get_or_insert_with(|| (Array1::zeros(params.len()), Array1::zeros(params.len())));
+            assert_eq!(m.len(), params.len(), "Adam::step called with a different parameter count");
+            self.t += 1;
+            *m = &*m * self.beta1 + grads * (1.0 - self.beta1);
+            *v = &*v * self.beta2 + grads.mapv(|g| g * g) * (1.0 - self.beta2);
+
+            // The moments start at zero, so early estimates are biased toward
+            // it; dividing by 1 - beta^t undoes that
+            let m_correction = 1.0 - self.beta1.powi(self.t);
+            let v_correction = 1.0 - self.beta2.powi(self.t);
+            ndarray::Zip::from(params)
+                .and(&*m)
+                .and(&*v)
+                .for_each(|p, &m, &v| {
+                    let m_hat = m / m_correction;
+                    let v_hat = v / v_correction;
+                    *p -= self.learning_rate * m_hat / (v_hat.sqrt() + self.epsilon);
+                });
+        }
+    }
+
+    /// RMSprop: per-parameter steps scaled by a running average of the
+    /// squared gradient
+    #[derive(Debug, Clone)]
+    pub struct RMSprop {
+        learning_rate: f64,
+        decay: f64,
+        epsilon: f64,
+        mean_square: Option<Array1<f64>>,
+    }
+
+    impl RMSprop {
+        /// `decay = 0.9`, `epsilon = 1e-8`
+        pub fn new(learning_rate: f64) -> Self {
+            Self {
+                learning_rate,
+                decay: 0.9,
+                epsilon: 1e-8,
+                mean_square: None,
+            }
+        }
+
+        pub fn with_decay(mut self, decay: f64) -> Self {
+            self.decay = decay;
+            self
+        }
+
+        pub fn with_epsilon(mut self, epsilon: f64) -> Self {
+            self.epsilon = epsilon;
+            self
+        }
+    }
+
+    impl Optimizer for RMSprop {
+        fn step(&mut self, params: &mut Array1<f64>, grads: &Array1<f64>) {
+            let mean_square = self.mean_square.get_or_insert_with(|| Array1::zeros(params.len()));
+            assert_eq!(mean_square.len(), params.len(), "RMSprop::step called with a different parameter count");
+            *mean_square = &*mean_square * self.decay + grads.mapv(|g| g * g) * (1.0 - self.decay);
+            ndarray::Zip::from(params)
+                .and(&*mean_square)
+                .and(grads)
+                .for_each(|p, &s, &g| *p -= self.learning_rate * g / (s.sqrt() + self.epsilon));
+        }
+    }
+}
+
+/// Reinforcement learning primitives
+pub mod reinforcement {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    /// Tabular Q-learning over `n_actions` discrete actions. Pairs that
+    /// were never updated have a Q-value of 0.
+    pub struct QLearning {
+        q_table: HashMap<(usize, usize), f64>,
+        learning_rate: f64,
+        discount_factor: f64,
+        n_actions: usize,
+    }
+
+    impl QLearning {
+        pub fn new(learning_rate: f64, discount_factor: f64, n_actions: usize) -> Self {
+            Self {
+                q_table: HashMap::new(),
+                learning_rate,
+                discount_factor,
+                n_actions,
+            }
+        }
+
+        pub fn n_actions(&self) -> usize {
+            self.n_actions
+        }
+
+        pub fn q_value(&self, state: usize, action: usize) -> f64 {
+            self.q_table.get(&(state, action)).copied().unwrap_or(0.0)
+        }
+
+        pub fn update(&mut self, state: usize, action: usize, reward: f64, next_state: usize) {
+            let current_q = self.q_value(state, action);
+            let next_max_q = self.get_max_q(next_state);
+            
+            let new_q = current_q + self.learning_rate * (reward + self.discount_factor * next_max_q - current_q);
+            self.q_table.insert((state, action), new_q);
+        }
+
+        /// Largest Q-value over every action in `state`; 0 when there are no
+        /// actions
+        fn get_max_q(&self, state: usize) -> f64 {
+            (0..self.n_actions)
+                .map(|action| self.q_value(state, action))
+                .reduce(f64::max)
+                .unwrap_or(0.0)
+        }
+
+        /// Epsilon-greedy policy: a uniformly random action with probability
+        /// `epsilon`, otherwise an action with the highest Q-value, ties
+        /// broken uniformly at random. A state with no learned values is all
+        /// ties, so its action is uniform. Panics if there are no actions.
+        pub fn select_action(&self, state: usize, epsilon: f64, rng: &mut impl Rng) -> usize {
+            epsilon_greedy(|action| self.q_value(state, action), self.n_actions, epsilon, rng)
+        }
+
+        /// Writes the Q-table and hyperparameters to `path` as JSON. Entries
+        /// are stored as `[state, action, q]` triples sorted by state and
+        /// action, and floats round-trip exactly.
+        pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+            let mut entries: Vec<(usize, usize, f64)> = self
+                .q_table
+                .iter()
+                .map(|(&(state, action), &q)| (state, action, q))
+                .collect();
+            entries.sort_by_key(|&(state, action, _)| (state, action));
+            let table = QTableFile {
+                learning_rate: self.learning_rate,
+                discount_factor: self.discount_factor,
+                n_actions: self.n_actions,
+                entries,
+            };
+            let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            serde_json::to_writer_pretty(file, &table)?;
+            Ok(())
+        }
+
+        pub fn load(path: &Path) -> Result<QLearning, anyhow::Error> {
+            let file = std::io::BufReader::new(std::fs::File::open(path)?);
+            let table: QTableFile = serde_json::from_reader(file)?;
+            Ok(QLearning {
+                q_table: table
+                    .entries
+                    .into_iter()
+                    .map(|(state, action, q)| ((state, action), q))
+                    .collect(),
+                learning_rate: table.learning_rate,
+                discount_factor: table.discount_factor,
+                n_actions: table.n_actions,
+            })
+        }
+    }
+
+    /// Tabular SARSA, the on-policy counterpart of `QLearning`: updates
+    /// bootstrap from the action the policy actually takes next rather than
+    /// the best one, so the values account for exploration
+    pub struct Sarsa {
+        q_table: HashMap<(usize, usize), f64>,
+        learning_rate: f64,
+        discount_factor: f64,
+        n_actions: usize,
+    }
+
+    impl Sarsa {
+        pub fn new(learning_rate: f64, discount_factor: f64, n_actions: usize) -> Self {
+            Self {
+                q_table: HashMap::new(),
+                learning_rate,
+                discount_factor,
+                n_actions,
+            }
+        }
+
+        pub fn n_actions(&self) -> usize {
+            self.n_actions
+        }
+
+        pub fn q_value(&self, state: usize, action: usize) -> f64 {
+            self.q_table.get(&(state, action)).copied().unwrap_or(0.0)
+        }
+
+        pub fn update(
+            &mut self,
+            state: usize,
+            action: usize,
+            reward: f64,
+            next_state: usize,
+            next_action: usize,
+        ) {
+            let current_q = self.q_value(state, action);
+            let next_q = self.q_value(next_state, next_action);
+
+            let new_q = current_q + self.learning_rate * (reward + self.discount_factor * next_q - current_q);
+            self.q_table.insert((state, action), new_q);
+        }
+
+        /// Epsilon-greedy policy, as `QLearning::select_action`
+        pub fn select_action(&self, state: usize, epsilon: f64, rng: &mut impl Rng) -> usize {
+            epsilon_greedy(|action| self.q_value(state, action), self.n_actions, epsilon, rng)
+        }
+    }
+
+    /// On-disk form of a Q-table, since JSON objects can't have tuple keys
+    #[derive(Serialize, Deserialize)]
+    struct QTableFile {
+        learning_rate: f64,
+        discount_factor: f64,
+        n_actions: usize,
+        entries: Vec<(usize, usize, f64)>,
+    }
+
+    fn epsilon_greedy(
+        q_value: impl Fn(usize) -> f64,
+        n_actions: usize,
+        epsilon: f64,
+        rng: &mut impl Rng,
+    ) -> usize {
+        assert!(n_actions > 0, "cannot select an action from an empty action space");
+        if rng.gen::<f64>() < epsilon {
+            return rng.gen_range(0..n_actions);
+        }
+        let values: Vec<f64> = (0..n_actions).map(q_value).collect();
+        let best = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let ties: Vec<usize> = (0..n_actions).filter(|&action| values[action] == best).collect();
+        ties[rng.gen_range(0..ties.len())]
+    }
+}
+
+/// Model evaluation and metrics
+pub mod evaluation {
+    use super::*;
+
+    /// Tolerances used by the evaluation metrics
+    #[derive(Debug, Clone, Copy)]
+    pub struct NumericConfig {
+        /// Maximum distance at which a prediction counts as matching its target
+        pub accuracy_tolerance: f64,
+        /// Predictions are clamped into `[eps, 1 - eps]` before taking logs
+        pub probability_epsilon: f64,
+    }
+
+    impl Default for NumericConfig {
+        fn default() -> Self {
+            Self {
+                accuracy_tolerance: 1e-6,
+                probability_epsilon: 1e-15,
+            }
+        }
+    }
+
+    /// Evaluates metrics with configurable numeric tolerances
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct MetricsEvaluator {
+        config: NumericConfig,
+    }
+
+    impl MetricsEvaluator {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn with_config(config: NumericConfig) -> Self {
+            Self { config }
+        }
+
+        pub fn accuracy_tolerance(mut self, tolerance: f64) -> Self {
+            self.config.accuracy_tolerance = tolerance;
+            self
+        }
+
+        pub fn probability_epsilon(mut self, epsilon: f64) -> Self {
+            self.config.probability_epsilon = epsilon;
+            self
+        }
+
+        pub fn config(&self) -> NumericConfig {
+            self.config
+        }
+
+        /// Fraction of predictions within `accuracy_tolerance` of their target
+        pub fn accuracy_within(&self, y_true: &Array1<f64>, y_pred: &Array1<f64>) -> f64 {
+            let correct = y_true.iter().zip(y_pred.iter())
+                .filter(|&(true_val, pred_val)| (true_val - pred_val).abs() < self.config.accuracy_tolerance)
+                .count();
+            correct as f64 / y_true.len() as f64
+        }
+
+        /// Binary cross-entropy with predictions clamped by `probability_epsilon`
+        pub fn cross_entropy(&self, y_true: &Array1<f64>, y_pred: &Array1<f64>) -> f64 {
+            let eps = self.config.probability_epsilon;
+            y_true.iter().zip(y_pred.iter())
+                .map(|(true_val, pred_val)| {
+                    let pred_val = pred_val.clamp(eps, 1.0 - eps);
+                    -true_val * pred_val.ln() - (1.0 - true_val) * (1.0 - pred_val).ln()
+                })
+                .sum::<f64>() / y_true.len() as f64
+        }
+    }
+
+    pub fn accuracy(y_true: &Array1<f64>, y_pred: &Array1<f64>) -> f64 {
+        MetricsEvaluator::new().accuracy_within(y_true, y_pred)
+    }
+
+    pub fn mean_squared_error(y_true: &Array1<f64>, y_pred: &Array1<f64>) -> f64 {
+        y_true.iter().zip(y_pred.iter())
+            .map(|(true_val, pred_val)| (true_val - pred_val).powi(2))
+            .sum::<f64>() / y_true.len() as f64
+    }
+
+    /// Binary cross-entropy with predictions clamped into `[1e-15, 1 - 1e-15]`
+    /// so predictions of exactly 0 or 1 give a finite loss. Use
+    /// `MetricsEvaluator::probability_epsilon` for a different bound.
+    pub fn cross_entropy(y_true: &Array1<f64>, y_pred: &Array1<f64>) -> f64 {
+        MetricsEvaluator::new().cross_entropy(y_true, y_pred)
+    }
+
+    /// Precision, recall and F1 of a binary classifier
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct ClassificationReport {
+        pub precision: f64,
+        pub recall: f64,
+        pub f1: f64,
+    }
+
+    /// True positive, false positive and false negative counts for 0/1
+    /// labels, where any label of at least 0.5 counts as positive
+    fn positive_counts(y_true: &Array1<f64>, y_pred: &Array1<f64>) -> (usize, usize, usize) {
+        y_true.iter().zip(y_pred.iter()).fold((0, 0, 0), |(tp, fp, fn_), (&t, &p)| {
+            match (t >= 0.5, p >= 0.5) {
+                (true, true) => (tp + 1, fp, fn_),
+                (false, true) => (tp, fp + 1, fn_),
+                (true, false) => (tp, fp, fn_ + 1),
+                (false, false) => (tp, fp, fn_),
+            }
+        })
+    }
+
+    fn ratio(numerator: usize, denominator: usize) -> f64 {
+        if denominator == 0 {
+            0.0
+        } else {
+            numerator as f64 / denominator as f64
+        }
+    }
+
+    /// Fraction of predicted positives that are positive; 0 when nothing is
+    /// predicted positive
+    pub fn precision(y_true: &Array1<f64>, y_pred: &Array1<f64>) -> f64 {
+        let (tp, fp, _) = positive_counts(y_true, y_pred);
+        ratio(tp, tp + fp)
+    }
+
+    /// Fraction of positives that are predicted positive; 0 when there are no
+    /// positives
+    pub fn recall(y_true: &Array1<f64>, y_pred: &Array1<f64>) -> f64 {
+        let (tp, _, fn_) = positive_counts(y_true, y_pred);
+        ratio(tp, tp + fn_)
+    }
+
+    /// Harmonic mean of precision and recall; 0 when both are 0
+    pub fn f1_score(y_true: &Array1<f64>, y_pred: &Array1<f64>) -> f64 {
+        binary_classification_report(y_true, y_pred).f1
+    }
+
+    pub fn binary_classification_report(y_true: &Array1<f64>, y_pred: &Array1<f64>) -> ClassificationReport {
+        let (tp, fp, fn_) = positive_counts(y_true, y_pred);
+        let precision = ratio(tp, tp + fp);
+        let recall = ratio(tp, tp + fn_);
+        let f1 = if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        };
+        ClassificationReport { precision, recall, f1 }
+    }
+
+    /// Counts of samples with true class `i` predicted as class `j` at
+    /// `[i, j]`. Labels must be integer-valued and in `0..n_classes`.
+    pub fn confusion_matrix(
+        y_true: &Array1<f64>,
+        y_pred: &Array1<f64>,
+        n_classes: usize,
+    ) -> Result<Array2<f64>, anyhow::Error> {
+        if y_true.len() != y_pred.len() {
+            anyhow::bail!(
+                "expected one prediction per target, got {} targets and {} predictions",
+                y_true.len(),
+                y_pred.len()
+            );
+        }
+        let class = |label: f64| -> Result<usize, anyhow::Error> {
+            if label.fract() != 0.0 || label < 0.0 || label >= n_classes as f64 {
+                anyhow::bail!("label {} is not a class in 0..{}", label, n_classes);
+            }
+            Ok(label as usize)
+        };
+        let mut matrix = Array2::zeros((n_classes, n_classes));
+        for (&t, &p) in y_true.iter().zip(y_pred.iter()) {
+            matrix[[class(t)?, class(p)?]] += 1.0;
+        }
+        Ok(matrix)
+    }
+
+    /// ROC curve of `scores` against 0/1 labels (positive at 0.5 or above):
+    /// false and true positive rates at every distinct score, from the
+    /// highest threshold down, starting at `(0, 0)` and ending at `(1, 1)`.
+    /// Samples with equal scores enter the curve together as a single point.
+    /// A rate whose class is absent from `y_true` stays at 0.
+    pub fn roc_curve(y_true: &Array1<f64>, scores: &Array1<f64>) -> (Vec<f64>, Vec<f64>) {
+        let mut samples: Vec<(f64, bool)> = scores
+            .iter()
+            .zip(y_true.iter())
+            .map(|(&score, &label)| (score, label >= 0.5))
+            .collect();
+        samples.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let positives = samples.iter().filter(|&&(_, positive)| positive).count();
+        let negatives = samples.len() - positives;
+
+        let (mut fpr, mut tpr) = (vec![0.0], vec![0.0]);
+        let (mut tp, mut fp) = (0, 0);
+        for (i, &(score, positive)) in samples.iter().enumerate() {
+            if positive {
+                tp += 1;
+            } else {
+                fp += 1;
+            }
+            if samples.get(i + 1).map(|&(next, _)| next) != Some(score) {
+                fpr.push(ratio(fp, negatives));
+                tpr.push(ratio(tp, positives));
+            }
+        }
+        (fpr, tpr)
+    }
+
+    /// Area under a curve by the trapezoidal rule, e.g. the output of
+    /// `roc_curve`
+    pub fn auc(fpr: &[f64], tpr: &[f64]) -> f64 {
+        fpr.windows(2)
+            .zip(tpr.windows(2))
+            .map(|(x, y)| (x[1] - x[0]) * (y[0] + y[1]) / 2.0)
+            .sum()
+    }
+
+    /// Mean softmax cross-entropy of a batch of logits against class labels,
+    /// together with its gradient w.r.t. the logits, computed in one pass with
+    /// the log-sum-exp trick so large logits cannot overflow
+    pub fn softmax_cross_entropy_with_logits(
+        logits: &Array2<f64>,
+        targets: &Array1<usize>,
+    ) -> Result<(f64, Array2<f64>), anyhow::Error> {
+        let (n_samples, n_classes) = logits.dim();
+        if n_samples == 0 || n_samples != targets.len() {
+            anyhow::bail!(
+                "expected a non-empty batch with one target per row, got {} rows and {} targets",
+                n_samples,
+                targets.len()
+            );
+        }
+
+        let scale = 1.0 / n_samples as f64;
+        let mut loss = 0.0;
+        let mut grad = Array2::zeros((n_samples, n_classes));
+        for (i, (row, &target)) in logits.outer_iter().zip(targets.iter()).enumerate() {
+            if target >= n_classes {
+                anyhow::bail!("target label {} out of range for {} classes", target, n_classes);
+            }
+            let max = row.fold(f64::NEG_INFINITY, |acc, &v| acc.max(v));
+            let log_sum_exp = max + row.mapv(|v| (v - max).exp()).sum().ln();
+            loss += log_sum_exp - row[target];
+            for (j, &logit) in row.iter().enumerate() {
+                grad[[i, j]] = (logit - log_sum_exp).exp() * scale;
+            }
+            grad[[i, target]] -= scale;
+        }
+
+        Ok((loss * scale, grad))
+    }
+
+    /// (train, validation) row indices for each cross-validation fold
+    pub type Folds = Vec<(Vec<usize>, Vec<usize>)>;
+
+    /// Splits a seeded shuffle of `0..n_samples` into `k` validation folds
+    /// whose sizes differ by at most one, pairing each with the remaining
+    /// rows as its training set
+    pub fn k_fold_indices(n_samples: usize, k: usize, seed: u64) -> Result<Folds, anyhow::Error> {
+        if k < 2 || k > n_samples {
+            anyhow::bail!("cannot split {} samples into {} folds", n_samples, k);
+        }
+        let mut indices: Vec<usize> = (0..n_samples).collect();
+        util::seeded_shuffle(&mut indices, seed);
+        Ok((0..k)
+            .map(|fold| {
+                let start = fold * n_samples / k;
+                let end = (fold + 1) * n_samples / k;
+                let validation = indices[start..end].to_vec();
+                let train = indices[..start]
+                    .iter()
+                    .chain(&indices[end..])
+                    .copied()
+                    .collect();
+                (train, validation)
+            })
+            .collect())
+    }
+}
+
+/// Feature scaling and dataset preparation
+pub mod preprocessing {
+    use super::*;
+    use ndarray::Axis;
+
+    /// Per-column `(x - offset) / scale`
+    #[derive(Debug, Clone)]
+    struct ColumnScaling {
+        offset: Array1<f64>,
+        scale: Array1<f64>,
+    }
+
+    impl ColumnScaling {
+        /// Constant columns get a scale of 1 so they are shifted but not
+        /// divided by zero
+        fn new(offset: Array1<f64>, spread: Array1<f64>) -> Self {
+            Self {
+                offset,
+                scale: spread.mapv(|s| if s > 0.0 && s.is_finite() { s } else { 1.0 }),
+            }
+        }
+
+        fn check<'a>(scaling: Option<&'a Self>, x: &Array2<f64>) -> Result<&'a Self, anyhow::Error> {
+            let scaling = scaling.ok_or_else(|| anyhow::anyhow!("scaler used before fit"))?;
+            if x.ncols() != scaling.offset.len() {
+                anyhow::bail!("scaler was fit on {} columns, got {}", scaling.offset.len(), x.ncols());
+            }
+            Ok(scaling)
+        }
+
+        fn transform(&self, x: &Array2<f64>) -> Array2<f64> {
+            (x - &self.offset) / &self.scale
+        }
+
+        fn inverse_transform(&self, x: &Array2<f64>) -> Array2<f64> {
+            x * &self.scale + &self.offset
+        }
+    }
+
+    /// Rescales each column to zero mean and unit (population) standard
+    /// deviation. Constant columns are centered but not scaled.
+    #[derive(Debug, Clone, Default)]
+    pub struct StandardScaler {
+        scaling: Option<ColumnScaling>,
+    }
+
+    impl StandardScaler {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn fit(&mut self, x: &Array2<f64>) -> Result<(), anyhow::Error> {
+            let mean = x
+                .mean_axis(Axis(0))
+                .ok_or_else(|| anyhow::anyhow!("cannot fit a scaler on an empty matrix"))?;
+            self.scaling = Some(ColumnScaling::new(mean, x.std_axis(Axis(0), 0.0)));
+            Ok(())
+        }
+
+        pub fn transform(&self, x: &Array2<f64>) -> Result<Array2<f64>, anyhow::Error> {
+            Ok(ColumnScaling::check(self.scaling.as_ref(), x)?.transform(x))
+        }
+
+        pub fn inverse_transform(&self, x: &Array2<f64>) -> Result<Array2<f64>, anyhow::Error> {
+            Ok(ColumnScaling::check(self.scaling.as_ref(), x)?.inverse_transform(x))
+        }
+
+        pub fn fit_transform(&mut self, x: &Array2<f64>) -> Result<Array2<f64>, anyhow::Error> {
+            self.fit(x)?;
+            self.transform(x)
+        }
+    }
+
+    /// Rescales each column linearly onto `[0, 1]` using its fitted minimum
+    /// and maximum. Constant columns are shifted to 0 but not scaled.
+    #[derive(Debug, Clone, Default)]
+    pub struct MinMaxScaler {
+        scaling: Option<ColumnScaling>,
+    }
+
+    impl MinMaxScaler {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn fit(&mut self, x: &Array2<f64>) -> Result<(), anyhow::Error> {
+            if x.nrows() == 0 {
+                anyhow::bail!("cannot fit a scaler on an empty matrix");
+            }
+            let min = x.fold_axis(Axis(0), f64::INFINITY, |&acc, &v| acc.min(v));
+            let max = x.fold_axis(Axis(0), f64::NEG_INFINITY, |&acc, &v| acc.max(v));
+            let range = &max - &min;
+            self.scaling = Some(ColumnScaling::new(min, range));
+            Ok(())
+        }
+
+        pub fn transform(&self, x: &Array2<f64>) -> Result<Array2<f64>, anyhow::Error> {
+            Ok(ColumnScaling::check(self.scaling.as_ref(), x)?.transform(x))
+        }
+
+        pub fn inverse_transform(&self, x: &Array2<f64>) -> Result<Array2<f64>, anyhow::Error> {
+            Ok(ColumnScaling::check(self.scaling.as_ref(), x)?.inverse_transform(x))
+        }
+
+        pub fn fit_transform(&mut self, x: &Array2<f64>) -> Result<Array2<f64>, anyhow::Error> {
+            self.fit(x)?;
+            self.transform(x)
+        }
+    }
+
+    /// Training and test partitions returned by `train_test_split`
+    pub type Split = (Array2<f64>, Array1<f64>, Array2<f64>, Array1<f64>);
+
+    /// Shuffles the rows with `util::seeded_shuffle(seed)` and holds out
+    /// `ceil(test_fraction * n)` of them, returning
+    /// `(x_train, y_train, x_test, y_test)` with rows of `x` and `y` kept
+    /// together
+    pub fn train_test_split(
+        x: &Array2<f64>,
+        y: &Array1<f64>,
+        test_fraction: f64,
+        seed: u64,
+    ) -> Result<Split, anyhow::Error> {
+        if !(test_fraction > 0.0 && test_fraction < 1.0) {
+            anyhow::bail!("test fraction must be between 0 and 1, got {}", test_fraction);
+        }
+        if x.nrows() != y.len() {
+            anyhow::bail!("x has {} rows but y has {} targets", x.nrows(), y.len());
+        }
+        let mut indices: Vec<usize> = (0..x.nrows()).collect();
+        util::seeded_shuffle(&mut indices, seed);
+        let n_test = (test_fraction * indices.len() as f64).ceil() as usize;
+        let (test, train) = indices.split_at(n_test);
+        Ok((
+            x.select(Axis(0), train),
+            y.select(Axis(0), train),
+            x.select(Axis(0), test),
+            y.select(Axis(0), test),
+        ))
+    }
+}
+
+/// Shared helpers used across the ML modules
+pub mod util {
+    use super::*;
+
+    /// Index of the largest value, resolving ties to the lowest index so every
+    /// caller breaks ties the same way. NaN values are never selected unless
+    /// all values are NaN.
+    pub fn argmax(values: &[f64]) -> Result<usize, anyhow::Error> {
+        if values.is_empty() {
+            anyhow::bail!("argmax of an empty slice");
+        }
+        let mut best = 0;
+        for (i, &value) in values.iter().enumerate().skip(1) {
+            if value > values[best] || (values[best].is_nan() && !value.is_nan()) {
+                best = i;
+            }
+        }
+        Ok(best)
+    }
+
+    /// The RNG used wherever the ML crates need seeded randomness. ChaCha8 is
+    /// used rather than `StdRng` because its output is stable across `rand`
+    /// releases, so a seed reproduces the same stream everywhere.
+    pub fn seeded_rng(seed: u64) -> ChaCha8Rng {
+        ChaCha8Rng::seed_from_u64(seed)
+    }
+
+    /// Shuffles `values` in place with a Fisher–Yates pass driven by
+    /// `seeded_rng(seed)`: for `i` from the last index down to 1, swap element
+    /// `i` with a uniformly chosen element in `0..=i`. Every feature that needs
+    /// a seeded permutation goes through this so the same seed always yields
+    /// the same order.
+    pub fn seeded_shuffle<T>(values: &mut [T], seed: u64) {
+        let mut rng = seeded_rng(seed);
+        for i in (1..values.len()).rev() {
+            let j = rng.gen_range(0..=i);
+            values.swap(i, j);
+        }
+    }
+
+    /// Row-wise `argmax` of a matrix
+    pub fn argmax_axis(values: &Array2<f64>) -> Result<Array1<usize>, anyhow::Error> {
+        if values.ncols() == 0 {
+            anyhow::bail!("argmax of a matrix with no columns");
+        }
+        values
+            .outer_iter()
+            .map(|row| argmax(&row.to_vec()))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Array1::from_vec)
+    }
+}
+
+/// Hyperparameter search with k-fold cross-validation
+pub mod model_selection {
+    use super::*;
+    use ndarray::Axis;
+
+    /// Hyperparameter values by name, kept sorted so searches are deterministic
+    pub type Hyperparameters = BTreeMap<String, f64>;
+
+    /// Candidate values for a single hyperparameter
+    #[derive(Debug, Clone)]
+    pub enum ParamSpace {
+        Values(Vec<f64>),
+        /// Grid search tries `steps` evenly spaced points from `low` to `high`;
+        /// random search samples uniformly from `[low, high]`
+        Range { low: f64, high: f64, steps: usize },
+    }
+
+    impl ParamSpace {
+        fn grid_points(&self) -> Vec<f64> {
+            match self {
+                ParamSpace::Values(values) => values.clone(),
+                ParamSpace::Range { low, high, steps } => match steps {
+                    0 => Vec::new(),
+                    1 => vec![*low],
+                    _ => (0..*steps)
+                        .map(|i| low + (high - low) * i as f64 / (*steps - 1) as f64)
+                        .collect(),
+                },
+            }
+        }
+
+        fn sample<R: Rng>(&self, rng: &mut R) -> Option<f64> {
+            match self {
+                ParamSpace::Values(values) if values.is_empty() => None,
+                ParamSpace::Values(values) => Some(values[rng.gen_range(0..values.len())]),
+                ParamSpace::Range { low, high, .. } => Some(rng.gen_range(*low..=*high)),
+            }
+        }
+    }
+
+    /// Best configuration found by a search, plus every trial that was scored
+    #[derive(Debug, Clone)]
+    pub struct SearchResult {
+        pub best_params: Hyperparameters,
+        pub best_score: f64,
+        pub trials: Vec<(Hyperparameters, f64)>,
+    }
+
+    /// Exhaustive search over the cartesian product of the parameter grids
+    pub struct GridSearch {
+        space: BTreeMap<String, ParamSpace>,
+        folds: usize,
+        seed: u64,
+    }
+
+    impl GridSearch {
+        pub fn new(folds: usize, seed: u64) -> Self {
+            Self {
+                space: BTreeMap::new(),
+                folds,
+                seed,
+            }
+        }
+
+        pub fn param(mut self, name: &str, space: ParamSpace) -> Self {
+            self.space.insert(name.to_string(), space);
+            self
+        }
+
+        /// Scores every grid point by k-fold cross-validation. `factory` builds
+        /// and trains a model on a training fold; `score` evaluates it on the
+        /// held-out fold, higher being better.
+        pub fn fit<M, Fac, Sc>(
+            &self,
+            x: &Array2<f64>,
+            y: &Array1<f64>,
+            factory: Fac,
+            score: Sc,
+        ) -> Result<SearchResult, anyhow::Error>
+        where
+            Fac: Fn(&Hyperparameters, &Array2<f64>, &Array1<f64>) -> M,
+            Sc: Fn(&M, &Array2<f64>, &Array1<f64>) -> f64,
+        {
+            if self.space.is_empty() {
+                anyhow::bail!("grid search needs at least one hyperparameter");
+            }
+            let mut candidates = vec![Hyperparameters::new()];
+            for (name, space) in &self.space {
+                let points = space.grid_points();
+                if points.is_empty() {
+                    anyhow::bail!("hyperparameter '{}' has no candidate values", name);
+                }
+                candidates = candidates
+                    .into_iter()
+                    .flat_map(|params| {
+                        points.iter().map(move |&value| {
+                            let mut params = params.clone();
+                            params.insert(name.clone(), value);
+                            params
+                        })
+                    })
+                    .collect();
+            }
+            search(candidates, x, y, self.folds, self.seed, &factory, &score)
+        }
+    }
+
+    /// Samples a fixed number of configurations from the parameter space
+    pub struct RandomSearch {
+        space: BTreeMap<String, ParamSpace>,
+        n_trials: usize,
+        folds: usize,
+        seed: u64,
+    }
+
+    impl RandomSearch {
+        pub fn new(n_trials: usize, folds: usize, seed: u64) -> Self {
+            Self {
+                space: BTreeMap::new(),
+                n_trials,
+                folds,
+                seed,
+            }
+        }
+
+        pub fn param(mut self, name: &str, space: ParamSpace) -> Self {
+            self.space.insert(name.to_string(), space);
+            self
+        }
+
+        /// Scores `n_trials` sampled configurations; the same seed always
+        /// samples the same configurations. See `GridSearch::fit`.
+        pub fn fit<M, Fac, Sc>(
+            &self,
+            x: &Array2<f64>,
+            y: &Array1<f64>,
+            factory: Fac,
+            score: Sc,
+        ) -> Result<SearchResult, anyhow::Error>
+        where
+            Fac: Fn(&Hyperparameters, &Array2<f64>, &Array1<f64>) -> M,
+            Sc: Fn(&M, &Array2<f64>, &Array1<f64>) -> f64,
+        {
+            if self.space.is_empty() || self.n_trials == 0 {
+                anyhow::bail!("random search needs at least one hyperparameter and one trial");
+            }
+            let mut rng = util::seeded_rng(self.seed);
+            let candidates = (0..self.n_trials)
+                .map(|_| {
+                    self.space
+                        .iter()
+                        .map(|(name, space)| {
+                            space
+                                .sample(&mut rng)
+                                .map(|value| (name.clone(), value))
+                                .ok_or_else(|| {
+                                    anyhow::anyhow!("hyperparameter '{}' has no candidate values", name)
+                                })
+                        })
+                        .collect::<Result<Hyperparameters, _>>()
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            search(candidates, x, y, self.folds, self.seed, &factory, &score)
+        }
+    }
+
+    fn search<M, Fac, Sc>(
+        candidates: Vec<Hyperparameters>,
+        x: &Array2<f64>,
+        y: &Array1<f64>,
+        folds: usize,
+        seed: u64,
+        factory: &Fac,
+        score: &Sc,
+    ) -> Result<SearchResult, anyhow::Error>
+    where
+        Fac: Fn(&Hyperparameters, &Array2<f64>, &Array1<f64>) -> M,
+        Sc: Fn(&M, &Array2<f64>, &Array1<f64>) -> f64,
+    {
+        if x.nrows() != y.len() {
+            anyhow::bail!("x has {} rows but y has {} targets", x.nrows(), y.len());
+        }
+        let splits = evaluation::k_fold_indices(x.nrows(), folds, seed)?;
+
+        let mut trials = Vec::with_capacity(candidates.len());
+        for params in candidates {
+            let mut total = 0.0;
+            for (train, validation) in &splits {
+                let model = factory(
+                    &params,
+                    &x.select(Axis(0), train),
+                    &y.select(Axis(0), train),
+                );
+                total += score(
+                    &model,
+                    &x.select(Axis(0), validation),
+                    &y.select(Axis(0), validation),
+                );
+            }
+            trials.push((params, total / splits.len() as f64));
+        }
+
+        let scores: Vec<f64> = trials.iter().map(|(_, score)| *score).collect();
+        let best = util::argmax(&scores).map_err(|_| anyhow::anyhow!("the search space has no candidates"))?;
+        if scores[best].is_nan() {
+            anyhow::bail!("every trial produced a NaN score");
+        }
+        let (best_params, best_score) = trials[best].clone();
+
+        Ok(SearchResult {
+            best_params,
+            best_score,
+            trials,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{arr1, arr2};
+
+    #[test]
+    fn argmax_resolves_ties_to_the_first_index() {
+        assert_eq!(util::argmax(&[1.0, 3.0, 3.0, 2.0]).unwrap(), 1);
+        assert_eq!(util::argmax(&[f64::NAN, 0.5, 0.5]).unwrap(), 1);
+        assert!(util::argmax(&[]).is_err());
+    }
+
+    #[test]
+    fn linear_regression_weights_are_drawn_independently() {
+        let model = algorithms::LinearRegression::<f64>::new(8);
+        let unit = |i: usize| Array1::from_shape_fn(8, |j| if i == j { 1.0 } else { 0.0 });
+        let weights: Vec<f64> = (0..8).map(|i| model.predict(&unit(i))).collect();
+        assert!(weights.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn linear_regression_converges_in_f32_and_f64() {
+        let x = Array2::from_shape_fn((20, 2), |(i, j)| ((i * (j + 1)) % 7) as f64 / 7.0);
+        let y = x.column(0).mapv(|v| 2.0 * v) - x.column(1).mapv(|v| v) + 0.5;
+        let (x32, y32) = (x.mapv(|v| v as f32), y.mapv(|v| v as f32));
+
+        let mut full = algorithms::LinearRegression::<f64>::new(2);
+        full.train(&x, &y, 0.5, 5000, 0.0, 0);
+        let mut single = algorithms::LinearRegression::<f32>::new(2);
+        single.train(&x32, &y32, 0.5, 5000, 0.0, 0);
+
+        assert!(full.r2_score(&x, &y) > 0.9999);
+        assert!(single.r2_score(&x32, &y32) > 0.999);
+        assert_eq!(full.parameter_bytes(), 2 * single.parameter_bytes());
+    }
+
+    #[test]
+    fn training_stops_early_once_the_loss_plateaus() {
+        let x = Array2::from_shape_fn((30, 1), |(i, _)| i as f64 / 30.0);
+        let y = x.column(0).mapv(|v| 4.0 * v - 1.0);
+        let max_epochs = 100_000;
+
+        let mut model = algorithms::LinearRegression::<f64>::new(1);
+        let epochs_run = model.train(&x, &y, 0.5, max_epochs, 1e-12, 5);
+        assert!(epochs_run < max_epochs / 10, "ran {epochs_run} epochs");
+        assert!(model.r2_score(&x, &y) > 0.999);
+
+        let mut model = algorithms::LinearRegression::<f64>::new(1);
+        assert_eq!(model.train(&x, &y, 0.5, 50, 1e-12, 0), 50);
+    }
+
+    #[test]
+    fn minibatch_training_matches_full_batch_error() {
+        let x = Array2::from_shape_fn((200, 2), |(i, j)| ((i * (j + 3)) % 17) as f64 / 17.0 - 0.5);
+        let y = x.column(0).mapv(|v| 3.0 * v) + x.column(1).mapv(|v| -2.0 * v) + 1.0;
+        let mse = |model: &algorithms::LinearRegression| {
+            let errors = x.rows().into_iter().zip(&y).map(|(row, &target)| model.predict(&row.to_owned()) - target);
+            errors.map(|e| e * e).sum::<f64>() / y.len() as f64
+        };
+
+        let mut full = algorithms::LinearRegression::<f64>::new(2);
+        full.train(&x, &y, 0.5, 2000, 0.0, 0);
+        let mut mini = algorithms::LinearRegression::<f64>::new(2);
+        mini.train_minibatch(&x, &y, 0.1, 300, 16, 7);
+        assert!(mse(&full) < 1e-6);
+        assert!(mse(&mini) < 1e-6);
+    }
+
+    #[test]
+    fn random_forest_beats_a_single_deep_tree_on_noisy_data() {
+        let mut rng = util::seeded_rng(5);
+        let mut sample = |n: usize| {
+            let x = Array2::from_shape_fn((n, 2), |_| rng.gen_range(-2.0..2.0));
+            let clean = x.map_axis(ndarray::Axis(1), |row| row[0].sin() + 0.5 * row[1]);
+            let noisy = clean.mapv(|v| v + rng.gen_range(-0.5..0.5));
+            (x, noisy, clean)
+        };
+        let (x, y, _) = sample(300);
+        let (x_test, _, y_test) = sample(200);
+        let mse = |model: &dyn Predict| {
+            let errors = x_test.rows().into_iter().zip(&y_test).map(|(row, &target)| model.predict(&row.to_owned()) - target);
+            errors.map(|e| e * e).sum::<f64>() / y_test.len() as f64
+        };
+
+        let mut tree = algorithms::DecisionTree::new(30, 2, algorithms::Criterion::Variance);
+        tree.fit(&x, &y);
+        let mut forest = algorithms::RandomForest::new(40, 30, 2, 0).with_seed(1);
+        forest.fit(&x, &y).unwrap();
+        assert!(mse(&forest) < 0.8 * mse(&tree), "{} vs {}", mse(&forest), mse(&tree));
+
+        let mut again = algorithms::RandomForest::new(40, 30, 2, 0).with_seed(1);
+        again.fit(&x, &y).unwrap();
+        let probe = x_test.row(0).to_owned();
+        assert_eq!(again.predict(&probe), forest.predict(&probe));
+
+        assert!(forest.fit(&Array2::zeros((0, 2)), &Array1::zeros(0)).is_err());
+        assert!(forest.fit(&x, &Array1::zeros(3)).is_err());
+        assert!(algorithms::RandomForest::new(0, 3, 2, 0).fit(&x, &y).is_err());
+    }
+
+    #[test]
+    fn decision_tree_recovers_a_piecewise_constant_function() {
+        let x = Array2::from_shape_fn((30, 1), |(i, _)| i as f64 / 10.0);
+        let y = x.column(0).mapv(|v| if v < 1.0 { 1.0 } else if v < 2.0 { 5.0 } else { -2.0 });
+        let mut tree = algorithms::DecisionTree::new(4, 2, algorithms::Criterion::Variance);
+        tree.fit(&x, &y);
+
+        for (v, expected) in [(0.3, 1.0), (1.5, 5.0), (2.7, -2.0)] {
+            assert_eq!(tree.predict(&arr1(&[v])), expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "DecisionTree::predict called before fit")]
+    fn decision_tree_predict_panics_before_fit() {
+        let tree = algorithms::DecisionTree::new(4, 2, algorithms::Criterion::Variance);
+        tree.predict(&arr1(&[0.0]));
+    }
+
+    #[test]
+    fn classification_trees_separate_two_linearly_separable_classes() {
+        let x = Array2::from_shape_fn((40, 2), |(i, j)| ((i * 7 + j * 13) % 40) as f64 / 40.0);
+        let y: Array1<f64> = x
+            .rows()
+            .into_iter()
+            .map(|row| if row[0] + 0.5 * row[1] > 0.6 { 1.0 } else { 0.0 })
+            .collect();
+
+        for criterion in [algorithms::Criterion::Entropy, algorithms::Criterion::Gini] {
+            let mut tree = algorithms::DecisionTree::new(10, 2, criterion);
+            tree.fit(&x, &y);
+            for (row, &label) in x.rows().into_iter().zip(&y) {
+                assert_eq!(tree.predict(&row.to_owned()), label, "{:?}", criterion);
+            }
+        }
+    }
+
+    #[test]
+    fn sequential_network_learns_xor() {
+        use neural_network::{DenseLayer, DropoutLayer, Sequential};
+        use simula_ai::{ActivationFunction, LossFunction};
+
+        let mut rng = util::seeded_rng(7);
+        let hidden = Array2::from_shape_fn((2, 4), |_| rng.gen_range(-1.0..1.0));
+        let output = Array2::from_shape_fn((4, 1), |_| rng.gen_range(-1.0..1.0));
+        let mut network = Sequential::new();
+        network
+            .add(DenseLayer::from_parameters(hidden, Array1::zeros(4), ActivationFunction::Tanh).unwrap())
+            .add(DenseLayer::from_parameters(output, Array1::zeros(1), ActivationFunction::Sigmoid).unwrap());
+        let x = arr2(&[[0.0, 0.0], [0.0, 1.0], [1.0, 0.0], [1.0, 1.0]]);
+        let y = arr2(&[[0.0], [1.0], [1.0], [0.0]]);
+
+        let first = network.train_step(&x, &y, &LossFunction::CrossEntropy, 0.5).unwrap();
+        let mut last = first;
+        for _ in 0..3000 {
+            last = network.train_step(&x, &y, &LossFunction::CrossEntropy, 0.5).unwrap();
+        }
+        assert!(last < 0.05, "loss went from {} to {}", first, last);
+        let predictions = network.predict(&x);
+        for (&prediction, &target) in predictions.iter().zip(y.iter()) {
+            assert_eq!(prediction >= 0.5, target == 1.0);
+        }
+        assert!(network.train_step(&x, &y, &LossFunction::Custom("hinge".to_string()), 0.1).is_err());
+
+        // `predict` leaves the caller's mode alone
+        let mut network = Sequential::new();
+        network.add(DropoutLayer::new(0.5, 3).unwrap());
+        let input = Array2::<f64>::ones((4, 64));
+        network.set_training(false);
+        network.predict(&input);
+        assert_eq!(network.forward(&input), input);
+        network.set_training(true);
+        assert_eq!(network.predict(&input), input);
+        assert_ne!(network.forward(&input), input);
+    }
+
+    #[test]
+    fn grid_search_picks_the_learning_rate_that_fits() {
+        use model_selection::{GridSearch, Hyperparameters, ParamSpace, RandomSearch};
+
+        let x = Array2::from_shape_fn((40, 1), |(i, _)| i as f64 / 40.0);
+        let y = x.column(0).mapv(|v| 3.0 * v + 1.0);
+        let factory = |params: &Hyperparameters, x: &Array2<f64>, y: &Array1<f64>| {
+            let mut model = algorithms::LinearRegression::new(1);
+            model.train(x, y, params["learning_rate"], 2000, 0.0, 0);
+            model
+        };
+        let score = |model: &algorithms::LinearRegression, x: &Array2<f64>, y: &Array1<f64>| {
+            let predictions: Array1<f64> = x.rows().into_iter().map(|row| model.predict(&row.to_owned())).collect();
+            -evaluation::mean_squared_error(y, &predictions)
+        };
+
+        let result = GridSearch::new(4, 1)
+            .param("learning_rate", ParamSpace::Values(vec![1e-5, 1e-3, 0.8]))
+            .fit(&x, &y, factory, score)
+            .unwrap();
+        assert_eq!(result.best_params["learning_rate"], 0.8);
+        assert!(result.best_score > -1e-3, "best score {}", result.best_score);
+
+        let space = ParamSpace::Range { low: 1e-4, high: 0.02, steps: 0 };
+        let first = RandomSearch::new(5, 4, 7).param("learning_rate", space.clone()).fit(&x, &y, factory, score).unwrap();
+        let second = RandomSearch::new(5, 4, 7).param("learning_rate", space).fit(&x, &y, factory, score).unwrap();
+        assert_eq!(first.trials.len(), 5);
+        let params = |result: &model_selection::SearchResult| result.trials.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>();
+        assert_eq!(params(&first), params(&second));
+    }
+
+    #[test]
+    fn softmax_cross_entropy_gradient_matches_finite_differences() {
+        let logits = arr2(&[[2.0, -1.0, 0.5], [0.1, 0.2, 3.0]]);
+        let targets = arr1(&[0, 2]);
+        let (_, gradient) = evaluation::softmax_cross_entropy_with_logits(&logits, &targets).unwrap();
+
+        let h = 1e-6;
+        for ((i, j), &analytic) in gradient.indexed_iter() {
+            let mut plus = logits.clone();
+            plus[[i, j]] += h;
+            let mut minus = logits.clone();
+            minus[[i, j]] -= h;
+            let (loss_plus, _) = evaluation::softmax_cross_entropy_with_logits(&plus, &targets).unwrap();
+            let (loss_minus, _) = evaluation::softmax_cross_entropy_with_logits(&minus, &targets).unwrap();
+            let numeric = (loss_plus - loss_minus) / (2.0 * h);
+            assert!((numeric - analytic).abs() < 1e-6, "d/dz[{},{}]: {} vs {}", i, j, numeric, analytic);
+        }
+
+        assert!(evaluation::softmax_cross_entropy_with_logits(&logits, &arr1(&[0, 3])).is_err());
+    }
+
+    #[test]
+    fn dense_layer_gradients_match_finite_differences() {
+        use neural_network::{DenseLayer, Layer};
+        use simula_ai::ActivationFunction;
+
+        // Loss is sum(output * upstream), so its output gradient is `upstream`
+        let input = arr2(&[[0.6, -0.4, 0.9], [-0.7, 0.3, 0.5]]);
+        let weights = arr2(&[[0.5, -0.8], [0.3, 0.7], [-0.6, 0.4]]);
+        let bias = arr1(&[0.1, -0.2]);
+        let upstream = arr2(&[[1.0, -0.5], [0.3, 2.0]]);
+        let h = 1e-6;
+
+        for activation in [
+            ActivationFunction::ReLU,
+            ActivationFunction::Sigmoid,
+            ActivationFunction::Tanh,
+            ActivationFunction::Softmax,
+        ] {
+            let loss = |weights: &Array2<f64>, bias: &Array1<f64>, input: &Array2<f64>| {
+                let mut layer = DenseLayer::from_parameters(weights.clone(), bias.clone(), activation.clone()).unwrap();
+                (layer.forward(input) * &upstream).sum()
+            };
+            let mut layer = DenseLayer::from_parameters(weights.clone(), bias.clone(), activation.clone()).unwrap();
+            layer.forward(&input);
+            let input_gradients = layer.backward(&upstream);
+
+            for ((i, j), &analytic) in layer.weight_gradients().indexed_iter() {
+                let (mut plus, mut minus) = (weights.clone(), weights.clone());
+                plus[[i, j]] += h;
+                minus[[i, j]] -= h;
+                let numeric = (loss(&plus, &bias, &input) - loss(&minus, &bias, &input)) / (2.0 * h);
+                assert!((numeric - analytic).abs() < 1e-6, "{:?} dW[{},{}]: {} vs {}", activation, i, j, numeric, analytic);
+            }
+            for (j, &analytic) in layer.bias_gradients().indexed_iter() {
+                let (mut plus, mut minus) = (bias.clone(), bias.clone());
+                plus[j] += h;
+                minus[j] -= h;
+                let numeric = (loss(&weights, &plus, &input) - loss(&weights, &minus, &input)) / (2.0 * h);
+                assert!((numeric - analytic).abs() < 1e-6, "{:?} db[{}]: {} vs {}", activation, j, numeric, analytic);
+            }
+            for ((i, j), &analytic) in input_gradients.indexed_iter() {
+                let (mut plus, mut minus) = (input.clone(), input.clone());
+                plus[[i, j]] += h;
+                minus[[i, j]] -= h;
+                let numeric = (loss(&weights, &bias, &plus) - loss(&weights, &bias, &minus)) / (2.0 * h);
+                assert!((numeric - analytic).abs() < 1e-6, "{:?} dx[{},{}]: {} vs {}", activation, i, j, numeric, analytic);
+            }
+        }
+    }
+
+    #[test]
+    fn scalers_invert_and_leave_constant_columns_finite() {
+        let x = arr2(&[[1.0, 5.0, -2.0], [2.0, 5.0, 0.5], [4.0, 5.0, 3.0], [9.0, 5.0, 1.0]]);
+        let close = |a: &Array2<f64>, b: &Array2<f64>| a.iter().zip(b).all(|(p, q)| (p - q).abs() < 1e-12);
+
+        let mut standard = preprocessing::StandardScaler::new();
+        let scaled = standard.fit_transform(&x).unwrap();
+        assert!(scaled.iter().all(|v| v.is_finite()));
+        assert!(scaled.column(1).iter().all(|&v| v == 0.0));
+        for column in [0, 2] {
+            let column = scaled.column(column);
+            assert!(column.mean().unwrap().abs() < 1e-12);
+            assert!((column.std(0.0) - 1.0).abs() < 1e-12);
+        }
+        assert!(close(&standard.inverse_transform(&scaled).unwrap(), &x));
+
+        let mut min_max = preprocessing::MinMaxScaler::new();
+        let scaled = min_max.fit_transform(&x).unwrap();
+        assert_eq!(scaled.column(0).to_vec(), vec![0.0, 0.125, 0.375, 1.0]);
+        assert!(scaled.column(1).iter().all(|&v| v == 0.0));
+        assert!(close(&min_max.inverse_transform(&scaled).unwrap(), &x));
+
+        assert!(preprocessing::StandardScaler::new().transform(&x).is_err());
+        assert!(standard.transform(&arr2(&[[1.0, 2.0]])).is_err());
+    }
+
+    #[test]
+    fn train_test_split_keeps_rows_aligned_and_is_seeded() {
+        let x = Array2::from_shape_fn((10, 2), |(i, j)| (i * 10 + j) as f64);
+        let y = Array1::from_shape_fn(10, |i| i as f64);
+
+        let (x_train, y_train, x_test, y_test) = preprocessing::train_test_split(&x, &y, 0.25, 7).unwrap();
+        assert_eq!((x_train.nrows(), y_train.len(), x_test.nrows(), y_test.len()), (7, 7, 3, 3));
+        for (row, target) in x_train.outer_iter().chain(x_test.outer_iter()).zip(y_train.iter().chain(&y_test)) {
+            assert_eq!(row.to_vec(), vec![target * 10.0, target * 10.0 + 1.0]);
+        }
+        let mut seen: Vec<f64> = y_train.iter().chain(&y_test).copied().collect();
+        seen.sort_by(f64::total_cmp);
+        assert_eq!(seen, y.to_vec());
+
+        let again = preprocessing::train_test_split(&x, &y, 0.25, 7).unwrap();
+        assert_eq!((again.1, again.3), (y_train.clone(), y_test.clone()));
+        let other = preprocessing::train_test_split(&x, &y, 0.25, 8).unwrap();
+        assert_ne!(other.1, y_train);
+
+        assert!(preprocessing::train_test_split(&x, &y, 0.0, 7).is_err());
+        assert!(preprocessing::train_test_split(&x, &y, 1.0, 7).is_err());
+        assert!(preprocessing::train_test_split(&x, &arr1(&[1.0, 2.0]), 0.25, 7).is_err());
+    }
+
+    /// `per_blob` points uniformly jittered by up to `spread` around each
+    /// center, blob by blob
+    fn blobs(centers: &[[f64; 2]], per_blob: usize, spread: f64, seed: u64) -> Array2<f64> {
+        let mut rng = util::seeded_rng(seed);
+        Array2::from_shape_fn((centers.len() * per_blob, 2), |(i, j)| {
+            centers[i / per_blob][j] + rng.gen_range(-spread..spread)
+        })
+    }
+
+    #[test]
+    fn kmeans_recovers_three_separated_blobs() {
+        let centers = [[0.0, 0.0], [10.0, 0.0], [0.0, 10.0]];
+        let x = blobs(&centers, 30, 1.0, 3);
+        let mut kmeans = algorithms::KMeans::new(3, 100).with_seed(5);
+        kmeans.fit(&x).unwrap();
+
+        let centroids = kmeans.centroids().unwrap();
+        for (blob, center) in centers.iter().enumerate() {
+            let label = kmeans.predict(&arr1(center));
+            let distance = (centroids[[label, 0]] - center[0]).hypot(centroids[[label, 1]] - center[1]);
+            assert!(distance < 0.5, "centroid {} is {} from blob {}", label, distance, blob);
+            for row in x.rows().into_iter().skip(blob * 30).take(30) {
+                assert_eq!(kmeans.predict(&row.to_owned()), label);
+            }
+        }
+        // Each jittered point is within sqrt(2) of its center
+        assert!(kmeans.inertia() < 90.0 * 2.0);
+
+        assert!(algorithms::KMeans::new(0, 10).fit(&x).is_err());
+        assert!(algorithms::KMeans::new(91, 10).fit(&x).is_err());
+    }
+
+    #[test]
+    fn logistic_regression_separates_linearly_separable_classes() {
+        let x = blobs(&[[-2.0, -1.0], [2.0, 1.0]], 25, 1.0, 11);
+        let y = Array1::from_shape_fn(50, |i| if i < 25 { 0.0 } else { 1.0 });
+        let mut model = algorithms::LogisticRegression::new(2);
+        let first = model.train(&x, &y, 0.5, 1).unwrap();
+        let last = model.train(&x, &y, 0.5, 500).unwrap();
+
+        assert!((first - std::f64::consts::LN_2).abs() < 1e-12);
+        assert!(last < 0.05, "loss {}", last);
+        let predictions = x.rows().into_iter().map(|row| model.predict(&row.to_owned())).collect::<Array1<f64>>();
+        assert_eq!(predictions, y);
+        assert!(model.predict_proba(&arr1(&[4.0, 2.0])) > 0.99);
+
+        // The penalty keeps the weights of a separable problem bounded
+        let mut regularized = algorithms::LogisticRegression::new(2).with_l2(0.1);
+        regularized.train(&x, &y, 0.5, 500).unwrap();
+        assert!(regularized.weights().dot(regularized.weights()) < model.weights().dot(model.weights()));
+        assert!(model.train(&x, &arr1(&[0.0]), 0.5, 1).is_err());
+    }
+
+    #[test]
+    fn pca_recovers_the_principal_axis() {
+        // Wide spread along (0.6, 0.8), narrow across it, off the origin
+        let mut rng = util::seeded_rng(9);
+        let mut x = Array2::zeros((200, 2));
+        for mut row in x.rows_mut() {
+            let along = rng.gen_range(-10.0..10.0);
+            let across = rng.gen_range(-0.5..0.5);
+            row.assign(&arr1(&[1.0 + 0.6 * along - 0.8 * across, -2.0 + 0.8 * along + 0.6 * across]));
+        }
+
+        let mut pca = algorithms::PCA::new(2);
+        let projected = pca.fit_transform(&x).unwrap();
+        let components = pca.components().unwrap();
+        assert!((components[[0, 0]] - 0.6).abs() < 1e-2 && (components[[0, 1]] - 0.8).abs() < 1e-2);
+        assert!(components.row(0).dot(&components.row(1)).abs() < 1e-9);
+        let ratio = pca.explained_variance_ratio().unwrap();
+        assert!(ratio[0] > 0.99 && (ratio.sum() - 1.0).abs() < 1e-9);
+        assert!(projected.column(0).std(1.0) > 5.0 && projected.column(1).std(1.0) < 0.5);
+
+        assert!(algorithms::PCA::new(3).fit(&x).is_err());
+        assert!(algorithms::PCA::new(1).transform(&x).is_err());
+    }
+
+    #[test]
+    fn adam_beats_sgd_on_an_ill_conditioned_quadratic() {
+        // f(p) = (100 p0^2 + 0.01 p1^2) / 2; SGD must keep its step below
+        // 2 / 100 to stay stable, which leaves p1 barely moving
+        let curvature = arr1(&[100.0, 0.01]);
+        let minimize = |optimizer: &mut dyn optimizers::Optimizer| {
+            let mut params = arr1(&[1.0, 1.0]);
+            for _ in 0..500 {
+                let grads = &params * &curvature;
+                optimizer.step(&mut params, &grads);
+            }
+            0.5 * (&params * &params * &curvature).sum()
+        };
+
+        let sgd = minimize(&mut optimizers::Sgd::new(0.019));
+        let adam = minimize(&mut optimizers::Adam::new(0.05));
+        assert!(adam < sgd / 100.0, "adam {} vs sgd {}", adam, sgd);
+        assert!(optimizers::from_config(&simula_ai::Optimizer::Custom("lion".to_string()), 0.1).is_err());
+    }
+
+    #[test]
+    fn batch_norm_normalizes_each_feature_over_the_batch() {
+        use neural_network::{BatchNorm, Layer};
+
+        let x = arr2(&[[1.0, 100.0], [3.0, 300.0], [5.0, 200.0], [7.0, 400.0]]);
+        let mut norm = BatchNorm::<f64>::new(2).with_epsilon(0.0);
+        let output = norm.forward(&x);
+        for column in output.columns() {
+            assert!(column.mean().unwrap().abs() < 1e-12);
+            assert!((column.var(0.0) - 1.0).abs() < 1e-12);
+        }
+        // Running statistics move a `momentum` step from (0, 1) towards the
+        // batch statistics
+        assert!((norm.running_mean() - &arr1(&[0.4, 25.0])).iter().all(|d| d.abs() < 1e-9));
+        assert!((norm.running_var() - &arr1(&[1.4, 1250.9])).iter().all(|d| d.abs() < 1e-9));
+
+        // Inference uses the running statistics, not the batch's own
+        norm.set_training(false);
+        let inference = norm.forward(&x);
+        assert!((inference[[0, 0]] - (1.0 - 0.4) / 1.4f64.sqrt()).abs() < 1e-12);
+        assert!(!norm.is_training());
+    }
+
+    #[test]
+    fn softmax_rows_sum_to_one_even_for_huge_logits() {
+        use neural_network::{DenseLayer, Layer};
+        use simula_ai::ActivationFunction;
+
+        let weights = arr2(&[[1.0, 0.0, -1.0], [0.0, 1.0, 2.0]]);
+        let mut layer = DenseLayer::from_parameters(weights, arr1(&[0.0, 0.0, 0.0]), ActivationFunction::Softmax).unwrap();
+        let input = arr2(&[[0.0, 0.0], [1.0, 2.0], [1000.0, -500.0], [-800.0, 900.0]]);
+        let output = layer.forward(&input);
+
+        for row in output.rows() {
+            assert!(row.iter().all(|p| p.is_finite() && (0.0..=1.0).contains(p)), "{:?}", row);
+            assert!((row.sum() - 1.0).abs() < 1e-12);
+        }
+        assert!(output.row(0).iter().all(|&p| (p - 1.0 / 3.0).abs() < 1e-12));
+        assert_eq!(output.row(2).to_vec(), vec![1.0, 0.0, 0.0]);
+        assert!(output[[1, 2]] > output[[1, 1]] && output[[1, 1]] > output[[1, 0]]);
+    }
+
+    #[test]
+    fn dropout_scales_survivors_in_training_and_is_the_identity_in_eval() {
+        use neural_network::{DropoutLayer, Layer};
+
+        let input = Array2::from_elem((200, 50), 2.0);
+        let mut dropout = DropoutLayer::<f64>::new(0.25, 4).unwrap();
+        let output = dropout.forward(&input);
+        assert!(output.iter().all(|&v| v == 0.0 || (v - 2.0 / 0.75).abs() < 1e-12));
+        let dropped = output.iter().filter(|&&v| v == 0.0).count() as f64 / output.len() as f64;
+        assert!((dropped - 0.25).abs() < 0.02, "dropped {}", dropped);
+        assert!((output.mean().unwrap() - 2.0).abs() < 0.05);
+        // The gradient only reaches the activations that were kept
+        let grad = dropout.backward(&Array2::ones(input.dim()));
+        assert_eq!(grad.mapv(|g| g == 0.0), output.mapv(|v| v == 0.0));
+
+        dropout.set_training(false);
+        assert!(!dropout.is_training());
+        assert_eq!(dropout.forward(&input), input);
+        assert_eq!(dropout.backward(&input), input);
+
+        let mut same_seed = DropoutLayer::<f64>::new(0.25, 4).unwrap();
+        assert_eq!(same_seed.forward(&input), output);
+        assert!(DropoutLayer::<f64>::new(1.0, 4).is_err());
+        assert!(DropoutLayer::<f64>::new(-0.1, 4).is_err());
+    }
+
+    /// Deterministic chain 0 - 1 - 2 - 3 where 3 is terminal. Action 0 moves
+    /// left (staying put at 0), action 1 moves right, and only reaching 3
+    /// pays a reward of 1.
+    fn chain_step(state: usize, action: usize) -> (usize, f64) {
+        let next = if action == 0 { state.saturating_sub(1) } else { state + 1 };
+        (next, if next == 3 { 1.0 } else { 0.0 })
+    }
+
+    #[test]
+    fn q_learning_converges_to_the_optimal_values_of_a_chain() {
+        let mut q = reinforcement::QLearning::new(0.5, 0.9, 2);
+        for _ in 0..500 {
+            for state in 0..3 {
+                for action in 0..2 {
+                    let (next, reward) = chain_step(state, action);
+                    q.update(state, action, reward, next);
+                }
+            }
+        }
+
+        // Q*(s, right) = 0.9^(2 - s); Q*(s, left) = 0.9 * max_a Q*(s - 1, a).
+        // The terminal state is never updated, so its values stay 0.
+        let optimal = [[0.729, 0.81], [0.729, 0.9], [0.81, 1.0]];
+        for (state, values) in optimal.iter().enumerate() {
+            for (action, &value) in values.iter().enumerate() {
+                assert!((q.q_value(state, action) - value).abs() < 1e-9, "Q({}, {})", state, action);
+            }
+        }
+        let mut rng = util::seeded_rng(0);
+        assert!((0..3).all(|state| q.select_action(state, 0.0, &mut rng) == 1));
+        assert_eq!((q.q_value(3, 0), q.q_value(3, 1)), (0.0, 0.0));
+    }
+
+    #[test]
+    fn q_learning_save_and_load_keep_the_learned_policy() {
+        let mut q = reinforcement::QLearning::new(0.3, 0.9, 2);
+        let mut rng = util::seeded_rng(2);
+        for _ in 0..50 {
+            let mut state = 0;
+            while state != 3 {
+                let action = q.select_action(state, 0.3, &mut rng);
+                let (next, reward) = chain_step(state, action);
+                q.update(state, action, reward, next);
+                state = next;
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("simula-ml-q-table-{}.json", std::process::id()));
+        q.save(&path).unwrap();
+        let mut loaded = reinforcement::QLearning::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.n_actions(), 2);
+        for state in 0..4 {
+            for action in 0..2 {
+                assert_eq!(loaded.q_value(state, action).to_bits(), q.q_value(state, action).to_bits());
+            }
+        }
+        let (mut original_rng, mut loaded_rng) = (util::seeded_rng(8), util::seeded_rng(8));
+        for _ in 0..20 {
+            for state in 0..4 {
+                assert_eq!(
+                    loaded.select_action(state, 0.2, &mut loaded_rng),
+                    q.select_action(state, 0.2, &mut original_rng)
+                );
+            }
+        }
+        // The hyperparameters survive too, so learning continues identically
+        q.update(0, 1, 1.0, 1);
+        loaded.update(0, 1, 1.0, 1);
+        assert_eq!(loaded.q_value(0, 1).to_bits(), q.q_value(0, 1).to_bits());
+        assert!(reinforcement::QLearning::load(&path).is_err());
+    }
+
+    #[test]
+    fn sarsa_learns_a_safer_cliff_walk_than_q_learning() {
+        // 6 x 4 grid from the bottom-left corner to the bottom-right one. The
+        // bottom row between them is a cliff: stepping in costs 100 and
+        // returns to the start. Every other step costs 1.
+        const WIDTH: usize = 6;
+        const HEIGHT: usize = 4;
+        const START: usize = (HEIGHT - 1) * WIDTH;
+        const GOAL: usize = HEIGHT * WIDTH - 1;
+        // Actions: 0 up, 1 right, 2 down, 3 left
+        let step = |state: usize, action: usize| -> (usize, f64) {
+            let (row, col) = (state / WIDTH, state % WIDTH);
+            let (row, col) = match action {
+                0 => (row.saturating_sub(1), col),
+                1 => (row, (col + 1).min(WIDTH - 1)),
+                2 => ((row + 1).min(HEIGHT - 1), col),
+                _ => (row, col.saturating_sub(1)),
+            };
+            if row == HEIGHT - 1 && col > 0 && col < WIDTH - 1 {
+                (START, -100.0)
+            } else {
+                (row * WIDTH + col, -1.0)
+            }
+        };
+        // Topmost row, farthest from the cliff, of the greedy walk to the goal
+        let topmost_row = |greedy: &dyn Fn(usize) -> usize| {
+            let mut state = START;
+            let mut rows = Vec::new();
+            while state != GOAL {
+                assert!(rows.len() < 50, "greedy policy never reaches the goal");
+                state = step(state, greedy(state)).0;
+                rows.push(state / WIDTH);
+            }
+            rows.into_iter().min().unwrap()
+        };
+
+        let mut rng = util::seeded_rng(11);
+        let mut q = reinforcement::QLearning::new(0.5, 1.0, 4);
+        let mut sarsa = reinforcement::Sarsa::new(0.5, 1.0, 4);
+        for _ in 0..2000 {
+            let mut state = START;
+            while state != GOAL {
+                let action = q.select_action(state, 0.1, &mut rng);
+                let (next, reward) = step(state, action);
+                q.update(state, action, reward, next);
+                state = next;
+            }
+
+            let mut state = START;
+            let mut action = sarsa.select_action(state, 0.1, &mut rng);
+            while state != GOAL {
+                let (next, reward) = step(state, action);
+                let next_action = sarsa.select_action(next, 0.1, &mut rng);
+                sarsa.update(state, action, reward, next, next_action);
+                state = next;
+                action = next_action;
+            }
+        }
+
+        let greedy_rng = util::seeded_rng(0);
+        let q_row = topmost_row(&|state| q.select_action(state, 0.0, &mut greedy_rng.clone()));
+        let sarsa_row = topmost_row(&|state| sarsa.select_action(state, 0.0, &mut greedy_rng.clone()));
+        // Q-learning values the optimal path along the cliff edge, while
+        // SARSA accounts for its own exploratory slips and climbs away
+        assert_eq!(q_row, HEIGHT - 2);
+        assert!(sarsa_row < HEIGHT - 2, "SARSA only climbed to row {}", sarsa_row);
+    }
+
+    #[test]
+    fn classification_report_matches_hand_computed_counts() {
+        // tp = 3, fp = 1, fn = 2, tn = 2
+        let y_true = arr1(&[1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0]);
+        let y_pred = arr1(&[1.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        assert_eq!(evaluation::precision(&y_true, &y_pred), 0.75);
+        assert_eq!(evaluation::recall(&y_true, &y_pred), 0.6);
+        let f1 = 2.0 * 0.75 * 0.6 / (0.75 + 0.6);
+        assert!((evaluation::f1_score(&y_true, &y_pred) - f1).abs() < 1e-12);
+        let report = evaluation::binary_classification_report(&y_true, &y_pred);
+        assert_eq!((report.precision, report.recall, report.f1), (0.75, 0.6, evaluation::f1_score(&y_true, &y_pred)));
+
+        // No predicted or actual positives: zero rather than NaN
+        let negatives = arr1(&[0.0, 0.0]);
+        assert_eq!(evaluation::precision(&arr1(&[1.0, 0.0]), &negatives), 0.0);
+        assert_eq!(evaluation::recall(&negatives, &arr1(&[1.0, 0.0])), 0.0);
+        assert_eq!(evaluation::f1_score(&negatives, &negatives), 0.0);
+    }
+
+    #[test]
+    fn confusion_matrix_counts_pairs_and_rejects_out_of_range_labels() {
+        let y_true = arr1(&[0.0, 1.0, 2.0, 2.0, 1.0, 0.0, 2.0]);
+        let y_pred = arr1(&[0.0, 2.0, 2.0, 2.0, 1.0, 1.0, 0.0]);
+        let matrix = evaluation::confusion_matrix(&y_true, &y_pred, 3).unwrap();
+        assert_eq!(matrix, arr2(&[[1.0, 1.0, 0.0], [0.0, 1.0, 1.0], [1.0, 0.0, 2.0]]));
+
+        assert!(evaluation::confusion_matrix(&y_true, &y_pred, 2).is_err());
+        for label in [-1.0, 0.5, 3.0, f64::NAN] {
+            assert!(evaluation::confusion_matrix(&arr1(&[label]), &arr1(&[0.0]), 3).is_err(), "accepted {}", label);
+            assert!(evaluation::confusion_matrix(&arr1(&[0.0]), &arr1(&[label]), 3).is_err(), "accepted {}", label);
+        }
+        assert!(evaluation::confusion_matrix(&y_true, &arr1(&[0.0]), 3).is_err());
+    }
+
+    #[test]
+    fn auc_is_one_for_a_perfect_separator_and_a_half_for_noise() {
+        let labels = arr1(&[0.0, 0.0, 1.0, 1.0]);
+        let (fpr, tpr) = evaluation::roc_curve(&labels, &arr1(&[0.1, 0.4, 0.35, 0.8]));
+        assert_eq!((fpr.clone(), tpr.clone()), (vec![0.0, 0.0, 0.5, 0.5, 1.0], vec![0.0, 0.5, 0.5, 1.0, 1.0]));
+        assert_eq!(evaluation::auc(&fpr, &tpr), 0.75);
+
+        let (fpr, tpr) = evaluation::roc_curve(&labels, &arr1(&[0.1, 0.2, 0.7, 0.9]));
+        assert_eq!(evaluation::auc(&fpr, &tpr), 1.0);
+        // Tied scores form a single step, so a constant score is the diagonal
+        let (fpr, tpr) = evaluation::roc_curve(&labels, &arr1(&[0.5; 4]));
+        assert_eq!((fpr.len(), evaluation::auc(&fpr, &tpr)), (2, 0.5));
+
+        let mut rng = util::seeded_rng(3);
+        let labels = Array1::from_shape_fn(5000, |i| (i % 2) as f64);
+        let scores = Array1::from_shape_fn(5000, |_| rng.gen::<f64>());
+        let (fpr, tpr) = evaluation::roc_curve(&labels, &scores);
+        assert!((evaluation::auc(&fpr, &tpr) - 0.5).abs() < 0.03);
+    }
+
+    #[test]
+    fn cross_entropy_stays_finite_at_certain_predictions() {
+        let confident_and_right = evaluation::cross_entropy(&arr1(&[1.0, 0.0]), &arr1(&[1.0, 0.0]));
+        assert!(confident_and_right.is_finite() && confident_and_right < 1e-12);
+
+        // Each certain mistake costs about -ln(eps) instead of infinity
+        let confident_and_wrong = evaluation::cross_entropy(&arr1(&[1.0, 0.0]), &arr1(&[0.0, 1.0]));
+        assert!((confident_and_wrong + 1e-15f64.ln()).abs() < 1e-3, "{}", confident_and_wrong);
+
+        let evaluator = evaluation::MetricsEvaluator::new().probability_epsilon(1e-3);
+        assert_eq!(evaluation::MetricsEvaluator::new().config().probability_epsilon, 1e-15);
+        assert!((evaluator.cross_entropy(&arr1(&[1.0]), &arr1(&[0.0])) + 1e-3f64.ln()).abs() < 1e-12);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn seeded_shuffle_is_a_reproducible_permutation() {
+        let mut first: Vec<u32> = (0..10).collect();
+        let mut second: Vec<u32> = (0..10).collect();
+        util::seeded_shuffle(&mut first, 42);
+        util::seeded_shuffle(&mut second, 42);
+        assert_eq!(first, second);
+        assert_ne!(first, (0..10).collect::<Vec<_>>());
+
+        first.sort_unstable();
+        assert_eq!(first, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn predictor_registry_dispatches_by_name() {
+        let x = arr2(&[[0.0], [1.0], [2.0], [3.0]]);
+        let y = arr1(&[1.0, 3.0, 5.0, 7.0]);
+        let mut linear = algorithms::LinearRegression::new(1);
+        linear.train(&x, &y, 0.1, 2000, 0.0, 0);
+        let mut tree = algorithms::DecisionTree::new(1, 2, algorithms::Criterion::Variance);
+        tree.fit(&x, &y);
+
+        let mut registry = PredictorRegistry::new();
+        registry.register("tree", Box::new(tree));
+        registry.register("linear", Box::new(linear));
+        assert_eq!(registry.names(), vec!["linear", "tree"]);
+
+        assert!((registry.predict("linear", &arr1(&[4.0])).unwrap() - 9.0).abs() < 1e-3);
+        assert_eq!(registry.predict("tree", &arr1(&[4.0])).unwrap(), 6.0);
+        assert!(registry.predict("missing", &arr1(&[4.0])).is_err());
+    }
+
+    #[test]
+    fn accuracy_tolerance_is_configurable() {
+        let y_true = arr1(&[1.0, 0.0]);
+        let y_pred = arr1(&[1.0 + 1e-7, 0.0]);
+        assert_eq!(evaluation::accuracy(&y_true, &y_pred), 1.0);
+
+        let strict = evaluation::MetricsEvaluator::new().accuracy_tolerance(1e-9);
+        assert_eq!(strict.accuracy_within(&y_true, &y_pred), 0.5);
+
+        let loose = evaluation::MetricsEvaluator::with_config(evaluation::NumericConfig {
+            accuracy_tolerance: 0.5,
+            ..Default::default()
+        });
+        assert_eq!(loose.accuracy_within(&y_true, &arr1(&[0.6, 0.4])), 1.0);
+    }
+
+    #[test]
+    fn multi_output_regression_fits_each_target() {
+        let x = Array2::from_shape_fn((30, 2), |(i, j)| ((i * (j + 2)) % 11) as f64 / 11.0);
+        let mut y = Array2::zeros((30, 2));
+        for i in 0..30 {
+            y[[i, 0]] = 2.0 * x[[i, 0]] + 1.0;
+            y[[i, 1]] = -3.0 * x[[i, 1]] + 0.5 * x[[i, 0]];
+        }
+        let mut model = algorithms::MultiOutputLinearRegression::new(2, 2);
+        model.train(&x, &y, 0.5, 5000).unwrap();
+
+        let predictions = model.predict_batch(&x).unwrap();
+        assert!((&predictions - &y).iter().all(|e| e.abs() < 1e-4));
+        assert!(model.predict(&arr1(&[1.0])).is_err());
+    }
+
+    #[test]
+    fn categorical_splits_isolate_a_middle_category() {
+        // Feature 0 is a category code in {0, 1, 2}; the target is 5 only for category 1
+        let x = arr2(&[[0.0, 0.3], [1.0, 0.1], [2.0, 0.9], [1.0, 0.5], [0.0, 0.2], [2.0, 0.4], [1.0, 0.8]]);
+        let y = arr1(&[0.0, 5.0, 0.0, 5.0, 0.0, 0.0, 5.0]);
+        let mut categorical =
+            algorithms::DecisionTree::new(1, 2, algorithms::Criterion::Variance).with_categorical(&[0]);
+        categorical.fit(&x, &y);
+        for (row, &target) in x.rows().into_iter().zip(&y) {
+            assert_eq!(categorical.predict(&row.to_owned()), target);
+        }
+
+        // A single threshold split cannot separate the middle category
+        let mut threshold = algorithms::DecisionTree::new(1, 2, algorithms::Criterion::Variance);
+        threshold.fit(&x, &y);
+        assert!(x
+            .rows()
+            .into_iter()
+            .zip(&y)
+            .any(|(row, &target)| threshold.predict(&row.to_owned()) != target));
+    }
+
+    #[test]
+    fn ridge_penalty_shrinks_the_weights() {
+        let x = Array2::from_shape_fn((50, 2), |(i, j)| ((i * (j + 2)) % 11) as f64 / 11.0);
+        let y: Array1<f64> = x.rows().into_iter().map(|row| 3.0 * row[0] - 2.0 * row[1] + 1.0).collect();
+        let mut plain = algorithms::LinearRegression::new(2);
+        plain.train(&x, &y, 0.01, 2000, 0.0, 0);
+        let mut ridge = algorithms::LinearRegression::new(2);
+        ridge.train_regularized(&x, &y, 0.01, 2000, 50.0);
+
+        let weight_norm = |model: &algorithms::LinearRegression| {
+            let bias = model.predict(&arr1(&[0.0, 0.0]));
+            let w0 = model.predict(&arr1(&[1.0, 0.0])) - bias;
+            let w1 = model.predict(&arr1(&[0.0, 1.0])) - bias;
+            (w0 * w0 + w1 * w1).sqrt()
+        };
+        assert!(weight_norm(&ridge) < 0.5 * weight_norm(&plain));
+    }
+
+    #[test]
+    fn r2_score_is_one_for_a_perfect_fit_and_handles_constant_targets() {
+        let x = Array2::from_shape_fn((30, 2), |(i, j)| ((i * (j + 2)) % 7) as f64 / 7.0);
+        let y: Array1<f64> = x.rows().into_iter().map(|row| 2.0 * row[0] - row[1] + 0.5).collect();
+        let mut model = algorithms::LinearRegression::new(2);
+        model.train(&x, &y, 0.5, 20000, 0.0, 0);
+        assert!((model.r2_score(&x, &y) - 1.0).abs() < 1e-6);
+
+        let flat = Array1::from_elem(30, 1.0);
+        assert_eq!(model.r2_score(&x, &flat), f64::NEG_INFINITY);
+        assert_eq!(model.r2_score(&Array2::zeros((30, 2)), &flat), 0.0);
+    }
+
+    #[test]
+    fn gradient_descent_recovers_the_generating_coefficients() {
+        let x = Array2::from_shape_fn((500, 3), |(i, j)| ((i * (2 * j + 3)) % 23) as f64 / 23.0 - 0.5);
+        let y: Array1<f64> = x
+            .rows()
+            .into_iter()
+            .map(|row| 4.0 * row[0] - 3.0 * row[1] + 0.5 * row[2] + 2.0)
+            .collect();
+        let mut model = algorithms::LinearRegression::new(3);
+        model.train(&x, &y, 0.5, 3000, 0.0, 0);
+
+        let bias = model.predict(&Array1::zeros(3));
+        assert!((bias - 2.0).abs() < 1e-6, "{}", bias);
+        for (j, weight) in [4.0, -3.0, 0.5].into_iter().enumerate() {
+            let mut unit = Array1::zeros(3);
+            unit[j] = 1.0;
+            assert!((model.predict(&unit) - bias - weight).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn seeded_linear_regression_trains_reproducibly() {
+        let x = arr2(&[[0.0, 1.0], [1.0, 0.5], [2.0, -1.0]]);
+        let y = arr1(&[1.0, 2.0, 4.0]);
+        let probe = arr1(&[0.5, 0.5]);
+        let train = |seed| {
+            let mut model = algorithms::LinearRegression::with_seed(2, seed);
+            model.train(&x, &y, 0.01, 10, 0.0, 0);
+            model.predict(&probe)
+        };
+        assert_eq!(train(3), train(3));
+        assert_ne!(train(3), train(4));
+    }
+}
@@ -1,10 +1,4 @@
-pub mod simulation;
-pub mod process;
 pub mod resource;
-pub mod event;
-pub mod time;
-pub mod memory;
-pub mod error;
 
 use thiserror::Error;
 
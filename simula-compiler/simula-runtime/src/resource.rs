@@ -0,0 +1,169 @@
+use crate::{Result, RuntimeError};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Usage of a resource pool right after an acquisition or release
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub time: f64,
+    pub resource: String,
+    pub in_use: usize,
+    /// How long the acquiring request waited; `None` for releases
+    pub waited: Option<f64>,
+}
+
+/// Resource usage emitted by `ResourcePool`s over a run, in time order
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceTrace {
+    pub records: Vec<ResourceUsage>,
+}
+
+impl ResourceTrace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_acquire(&mut self, time: f64, resource: &str, in_use: usize, waited: f64) {
+        self.records.push(ResourceUsage {
+            time,
+            resource: resource.to_string(),
+            in_use,
+            waited: Some(waited),
+        });
+    }
+
+    pub fn record_release(&mut self, time: f64, resource: &str, in_use: usize) {
+        self.records.push(ResourceUsage {
+            time,
+            resource: resource.to_string(),
+            in_use,
+            waited: None,
+        });
+    }
+
+    pub fn for_resource<'a>(&'a self, resource: &'a str) -> impl Iterator<Item = &'a ResourceUsage> {
+        self.records.iter().filter(move |usage| usage.resource == resource)
+    }
+}
+
+/// A pool of `capacity` identical units. Requests are granted immediately
+/// while units are free and otherwise queue in FIFO order until a release.
+/// Every grant and release is recorded in the pool's `ResourceTrace`.
+#[derive(Debug, Clone)]
+pub struct ResourcePool {
+    name: String,
+    capacity: usize,
+    in_use: usize,
+    /// Request times of queued requests, oldest first
+    waiting: VecDeque<f64>,
+    trace: ResourceTrace,
+}
+
+impl ResourcePool {
+    pub fn new(name: &str, capacity: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            capacity,
+            in_use: 0,
+            waiting: VecDeque::new(),
+            trace: ResourceTrace::new(),
+        }
+    }
+
+    /// Requests one unit at `time`. Returns true if it was granted
+    /// immediately, or false if the request was queued.
+    pub fn acquire(&mut self, time: f64) -> bool {
+        if self.in_use < self.capacity {
+            self.in_use += 1;
+            self.trace.record_acquire(time, &self.name, self.in_use, 0.0);
+            true
+        } else {
+            self.waiting.push_back(time);
+            false
+        }
+    }
+
+    /// Returns one unit at `time`. If a request is queued the unit passes
+    /// straight to the oldest one, recorded as a release followed by an
+    /// acquisition that waited since its request.
+    pub fn release(&mut self, time: f64) -> Result<()> {
+        if self.in_use == 0 {
+            return Err(RuntimeError::Resource(format!(
+                "release of {} at time {} with no units in use",
+                self.name, time
+            )));
+        }
+        self.in_use -= 1;
+        self.trace.record_release(time, &self.name, self.in_use);
+        if let Some(requested) = self.waiting.pop_front() {
+            self.in_use += 1;
+            self.trace.record_acquire(time, &self.name, self.in_use, time - requested);
+        }
+        Ok(())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn in_use(&self) -> usize {
+        self.in_use
+    }
+
+    /// Number of queued requests
+    pub fn waiting(&self) -> usize {
+        self.waiting.len()
+    }
+
+    pub fn trace(&self) -> &ResourceTrace {
+        &self.trace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool_grants_up_to_capacity_and_queues_the_rest_in_order() {
+        let mut pool = ResourcePool::new("gpu", 2);
+        assert!(pool.acquire(0.0));
+        assert!(pool.acquire(1.0));
+        assert!(!pool.acquire(2.0));
+        assert!(!pool.acquire(3.0));
+        assert_eq!((pool.in_use(), pool.waiting()), (2, 2));
+
+        // Each release hands the unit straight to the oldest queued request
+        pool.release(5.0).unwrap();
+        assert_eq!((pool.in_use(), pool.waiting()), (2, 1));
+        pool.release(6.0).unwrap();
+        pool.release(7.0).unwrap();
+        pool.release(8.0).unwrap();
+        assert_eq!((pool.in_use(), pool.waiting()), (0, 0));
+        assert!(matches!(pool.release(9.0), Err(RuntimeError::Resource(_))));
+
+        let records: Vec<(f64, usize, Option<f64>)> = pool
+            .trace()
+            .for_resource("gpu")
+            .map(|usage| (usage.time, usage.in_use, usage.waited))
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                (0.0, 1, Some(0.0)),
+                (1.0, 2, Some(0.0)),
+                (5.0, 1, None),
+                (5.0, 2, Some(3.0)),
+                (6.0, 1, None),
+                (6.0, 2, Some(3.0)),
+                (7.0, 1, None),
+                (8.0, 0, None),
+            ]
+        );
+        assert_eq!(pool.trace().for_resource("cpu").count(), 0);
+    }
+}
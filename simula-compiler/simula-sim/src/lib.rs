@@ -1,215 +1,3825 @@
-use std::collections::HashMap;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::future::Future;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
-use rand::Rng;
-use rand_distr::{Normal, Distribution};
-use simula_ai::{AIModel, ParameterValue};
-use simula_ml::algorithms;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Exp, Normal, Distribution, Weibull};
+use simula_ai::AIModel;
+use simula_runtime::RuntimeError;
+use serde::{Deserialize, Serialize};
+use observer::{Observer, RunSummary};
 
 /// Discrete event simulation engine for AI models
 pub struct SimulationEngine {
     time: f64,
-    events: Vec<Event>,
+    events: BinaryHeap<Reverse<ScheduledEvent>>,
+    barriers: Vec<PendingBarrier>,
+    next_seq: u64,
+    next_event_id: u64,
     models: HashMap<String, AIModel>,
     metrics: HashMap<String, Vec<f64>>,
+    streaming_metrics: Option<HashMap<String, statistics::StreamingSummary>>,
+    rng: SimRng,
+    seed: Option<u64>,
+    observers: Vec<Box<dyn Observer>>,
+    handlers: HashMap<String, EventHandler>,
+    evaluators: HashMap<String, Evaluator>,
+    guards: HashMap<EventId, EventGuard>,
+    sub_simulations: HashMap<String, SubSimulation>,
+    depth: usize,
+    declared_metrics: Option<HashSet<String>>,
+    undeclared_metrics: BTreeSet<String>,
+    event_costs: HashMap<EventTypeKind, f64>,
+    arrival_latency: Option<(f64, f64)>,
+    inactive_models: HashSet<String>,
+    profile: Option<HashMap<String, Duration>>,
+    trace: Option<observer::TraceObserver>,
+    progress: Option<Progress>,
+    past_event_policy: PastEventPolicy,
+    tie_break: TieBreak,
+    warmup_until: f64,
 }
 
-#[derive(Debug, Clone)]
+/// Callback reporting the simulation time as the run passes each progress
+/// interval
+pub type ProgressCallback = Box<dyn FnMut(f64) + Send>;
+
+struct Progress {
+    every: f64,
+    next: f64,
+    callback: ProgressCallback,
+}
+
+impl Progress {
+    /// First multiple of `every` strictly after `time`
+    fn boundary_after(&self, time: f64) -> f64 {
+        ((time / self.every).floor() + 1.0) * self.every
+    }
+}
+
+/// Maximum nesting of sub-simulations below a top-level engine
+pub const MAX_SUB_SIMULATION_DEPTH: usize = 8;
+
+/// Samples `run_with_stream` buffers for a slow receiver before dropping
+pub const METRIC_STREAM_CAPACITY: usize = 1024;
+
+/// A child engine run for `horizon` simulated time units whenever `trigger`
+/// fires for the model it is registered against
+pub struct SubSimulation {
+    engine: SimulationEngine,
+    trigger: EventType,
+    horizon: f64,
+    lifted_metrics: Vec<String>,
+}
+
+impl SubSimulation {
+    pub fn new(engine: SimulationEngine, trigger: EventType, horizon: f64) -> Self {
+        Self {
+            engine,
+            trigger,
+            horizon,
+            lifted_metrics: Vec::new(),
+        }
+    }
+
+    /// Copies samples of the child's `metric` into the parent as
+    /// `<model_id>.<metric>` after every triggered run
+    pub fn lift_metric(mut self, metric: &str) -> Self {
+        self.lifted_metrics.push(metric.to_string());
+        self
+    }
+
+    pub fn engine(&self) -> &SimulationEngine {
+        &self.engine
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Event {
-    time: f64,
-    event_type: EventType,
-    model_id: String,
+    pub time: f64,
+    pub event_type: EventType,
+    pub model_id: String,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub deadline: Option<f64>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum EventType {
     ModelUpdate,
     DataArrival,
     TrainingStep,
     Evaluation,
     Custom(String),
+    /// Synchronization point for the listed models, see
+    /// `SimulationEngine::schedule_barrier`
+    Barrier(Vec<String>),
 }
 
-impl SimulationEngine {
-    pub fn new() -> Self {
-        Self {
-            time: 0.0,
-            events: Vec::new(),
-            models: HashMap::new(),
-            metrics: HashMap::new(),
+/// An `EventType` with any `Custom` payload dropped, for keying maps by kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventTypeKind {
+    ModelUpdate,
+    DataArrival,
+    TrainingStep,
+    Evaluation,
+    Custom,
+    Barrier,
+}
+
+impl From<&EventType> for EventTypeKind {
+    fn from(event_type: &EventType) -> Self {
+        event_type.kind()
+    }
+}
+
+impl EventType {
+    /// The variant without its payload, so two `Custom` types with different
+    /// names share a kind
+    pub fn kind(&self) -> EventTypeKind {
+        match self {
+            EventType::ModelUpdate => EventTypeKind::ModelUpdate,
+            EventType::DataArrival => EventTypeKind::DataArrival,
+            EventType::TrainingStep => EventTypeKind::TrainingStep,
+            EventType::Evaluation => EventTypeKind::Evaluation,
+            EventType::Custom(_) => EventTypeKind::Custom,
+            EventType::Barrier(_) => EventTypeKind::Barrier,
         }
     }
+}
 
-    pub fn add_model(&mut self, model: AIModel) {
-        self.models.insert(model.name.clone(), model);
+impl Event {
+    pub fn builder() -> EventBuilder {
+        EventBuilder::new()
+    }
+}
+
+/// Builds an `Event`, validating its fields instead of trusting a struct literal
+#[derive(Debug, Clone, Default)]
+pub struct EventBuilder {
+    time: Option<f64>,
+    event_type: Option<EventType>,
+    model_id: Option<String>,
+    priority: i32,
+    deadline: Option<f64>,
+}
+
+impl EventBuilder {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    pub fn schedule_event(&mut self, event: Event) {
-        self.events.push(event);
-        self.events.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+    pub fn time(mut self, time: f64) -> Self {
+        self.time = Some(time);
+        self
     }
 
-    pub async fn run(&mut self, end_time: f64) -> Result<(), anyhow::Error> {
-        while self.time < end_time && !self.events.is_empty() {
-            let event = self.events.remove(0);
-            self.time = event.time;
-            self.process_event(event)?;
+    pub fn event_type(mut self, event_type: EventType) -> Self {
+        self.event_type = Some(event_type);
+        self
+    }
+
+    pub fn model_id(mut self, model_id: impl Into<String>) -> Self {
+        self.model_id = Some(model_id.into());
+        self
+    }
+
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn deadline(mut self, deadline: f64) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn build(self) -> Result<Event, RuntimeError> {
+        let time = self
+            .time
+            .ok_or_else(|| RuntimeError::Event("event time is required".to_string()))?;
+        if !time.is_finite() || time < 0.0 {
+            return Err(RuntimeError::Event(format!(
+                "event time must be finite and non-negative, got {}",
+                time
+            )));
         }
-        Ok(())
+        let event_type = self
+            .event_type
+            .ok_or_else(|| RuntimeError::Event("event type is required".to_string()))?;
+        let model_id = self.model_id.unwrap_or_default();
+        if model_id.is_empty() {
+            return Err(RuntimeError::Event("event model id must not be empty".to_string()));
+        }
+        if let Some(deadline) = self.deadline {
+            if !deadline.is_finite() || deadline < time {
+                return Err(RuntimeError::Event(format!(
+                    "event deadline {} must be finite and not before its time {}",
+                    deadline, time
+                )));
+            }
+        }
+        Ok(Event {
+            time,
+            event_type,
+            model_id,
+            priority: self.priority,
+            deadline: self.deadline,
+        })
     }
+}
 
-    fn process_event(&mut self, event: Event) -> Result<(), anyhow::Error> {
-        if let Some(model) = self.models.get_mut(&event.model_id) {
-            match event.event_type {
-                EventType::ModelUpdate => self.update_model(model)?,
-                EventType::DataArrival => self.process_data(model)?,
-                EventType::TrainingStep => self.train_model(model)?,
-                EventType::Evaluation => self.evaluate_model(model)?,
-                EventType::Custom(_) => {
-                    // Handle custom event types
-                }
+/// Inter-arrival time distributions for stochastically generated events
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum ArrivalDist {
+    Exponential { rate: f64 },
+    Weibull { shape: f64, scale: f64 },
+    Normal { mean: f64, std: f64 },
+}
+
+impl ArrivalDist {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        match *self {
+            ArrivalDist::Exponential { rate } if !is_positive(rate) => {
+                anyhow::bail!("exponential arrival rate must be positive, got {}", rate)
             }
+            ArrivalDist::Weibull { shape, scale } if !is_positive(shape) || !is_positive(scale) => {
+                anyhow::bail!(
+                    "Weibull arrival shape and scale must be positive, got shape {} and scale {}",
+                    shape,
+                    scale
+                )
+            }
+            ArrivalDist::Normal { mean, std } if !is_positive(mean) || !std.is_finite() || std < 0.0 => {
+                anyhow::bail!(
+                    "normal arrival mean must be positive and std non-negative, got mean {} and std {}",
+                    mean,
+                    std
+                )
+            }
+            _ => Ok(()),
         }
-        Ok(())
     }
 
-    fn update_model(&mut self, model: &mut AIModel) -> Result<(), anyhow::Error> {
-        // Implement model update logic
-        Ok(())
+    /// Draws an inter-arrival time, clamping negative normal draws to zero
+    fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        match *self {
+            ArrivalDist::Exponential { rate } => Exp::new(rate).unwrap().sample(rng),
+            ArrivalDist::Weibull { shape, scale } => Weibull::new(scale, shape).unwrap().sample(rng),
+            ArrivalDist::Normal { mean, std } => Normal::new(mean, std).unwrap().sample(rng).max(0.0),
+        }
     }
+}
 
-    fn process_data(&mut self, model: &mut AIModel) -> Result<(), anyhow::Error> {
-        // Implement data processing logic
-        Ok(())
+/// The engine's random stream. In antithetic mode every output word is
+/// complemented, so a uniform draw `u` becomes `1 - u` and an engine paired
+/// with another on the same seed draws the mirrored sequence.
+#[derive(Debug, Clone)]
+struct SimRng {
+    inner: ChaCha8Rng,
+    antithetic: bool,
+}
+
+impl SimRng {
+    fn new(inner: ChaCha8Rng) -> Self {
+        Self {
+            inner,
+            antithetic: false,
+        }
     }
+}
 
-    fn train_model(&mut self, model: &mut AIModel) -> Result<(), anyhow::Error> {
-        // Implement model training logic
-        Ok(())
+impl RngCore for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        let value = self.inner.next_u32();
+        if self.antithetic { !value } else { value }
     }
 
-    fn evaluate_model(&mut self, model: &mut AIModel) -> Result<(), anyhow::Error> {
-        // Implement model evaluation logic
+    fn next_u64(&mut self) -> u64 {
+        let value = self.inner.next_u64();
+        if self.antithetic { !value } else { value }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.inner.fill_bytes(dest);
+        if self.antithetic {
+            for byte in dest {
+                *byte = !*byte;
+            }
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
         Ok(())
     }
 }
 
-/// Parallel simulation capabilities
-pub mod parallel {
-    use super::*;
-    use rayon::prelude::*;
+fn is_positive(value: f64) -> bool {
+    value.is_finite() && value > 0.0
+}
 
-    pub struct ParallelSimulation {
-        engines: Vec<SimulationEngine>,
-        num_workers: usize,
+/// Callback run for `EventType::Custom` events with the model the event
+/// targets, the current simulation time and a sink for the metrics it records
+pub type EventHandler = Box<dyn FnMut(&mut AIModel, f64, &mut MetricSink) -> Result<(), anyhow::Error> + Send>;
+
+/// Metrics recorded by a custom handler. The engine records them once the
+/// handler returns, flagging any name no model declares.
+#[derive(Debug, Default)]
+pub struct MetricSink {
+    samples: Vec<(String, f64)>,
+}
+
+impl MetricSink {
+    pub fn record(&mut self, name: &str, value: f64) {
+        self.samples.push((name.to_string(), value));
     }
+}
 
-    impl ParallelSimulation {
-        pub fn new(num_workers: usize) -> Self {
-            Self {
-                engines: vec![SimulationEngine::new(); num_workers],
-                num_workers,
-            }
-        }
+/// Scores a model for `EventType::Evaluation` events, returning its accuracy
+/// in `[0, 1]` on whatever held-out data the closure captures
+pub type Evaluator = Box<dyn FnMut(&AIModel) -> Result<f64, anyhow::Error> + Send>;
 
-        pub async fn run_parallel(&mut self, end_time: f64) -> Result<(), anyhow::Error> {
-            let mut handles = Vec::new();
-            
-            for engine in &mut self.engines {
-                let handle = tokio::spawn(async move {
-                    engine.run(end_time).await
-                });
-                handles.push(handle);
-            }
+/// Predicate deciding at fire time whether a conditional event runs. Guards
+/// only inspect the model and must not rely on side effects.
+pub type EventGuard = Box<dyn Fn(&AIModel) -> bool + Send>;
 
-            for handle in handles {
-                handle.await??;
-            }
+/// Handle to a recurring or conditional event, used to cancel it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EventId(u64);
 
-            Ok(())
-        }
+/// What `schedule_event` does with an event timed before the current clock
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PastEventPolicy {
+    /// Fail with `RuntimeError::Time`
+    #[default]
+    Reject,
+    /// Move the event to the current time
+    ClampToNow,
+}
 
-        pub fn aggregate_results(&self) -> HashMap<String, Vec<f64>> {
-            let mut aggregated = HashMap::new();
-            
-            for engine in &self.engines {
-                for (metric, values) in &engine.metrics {
-                    aggregated
-                        .entry(metric.clone())
-                        .or_insert_with(Vec::new)
-                        .extend(values);
-                }
-            }
+/// How the queue orders events scheduled for the same time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TieBreak {
+    /// In the order they were scheduled
+    #[default]
+    Fifo,
+    /// Highest `Event::priority` first, then in scheduling order
+    ByPriority,
+    /// Ascending `model_id`, then in scheduling order
+    ByModelId,
+}
 
-            aggregated
+/// An event in the queue along with how it should be rescheduled once fired
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ScheduledEvent {
+    event: Event,
+    arrival: Option<ArrivalDist>,
+    /// Set once a `DataArrival` has been pushed back by the arrival latency
+    #[serde(default)]
+    delayed: bool,
+    /// When a delayed event was originally due
+    #[serde(default)]
+    due: Option<f64>,
+    /// Interval after which a recurring event fires again
+    period: Option<f64>,
+    id: Option<EventId>,
+    /// Whether the event only runs if its guard accepts the model
+    #[serde(default)]
+    conditional: bool,
+    /// Insertion order, breaking ties between events at the same time
+    seq: u64,
+    /// The engine's policy when the event was queued
+    #[serde(default)]
+    tie_break: TieBreak,
+}
+
+impl ScheduledEvent {
+    fn new(event: Event) -> Self {
+        Self {
+            event,
+            arrival: None,
+            delayed: false,
+            due: None,
+            period: None,
+            id: None,
+            conditional: false,
+            seq: 0,
+            tie_break: TieBreak::default(),
         }
     }
+
+    /// When the event was due, before any arrival latency
+    fn due_time(&self) -> f64 {
+        self.due.unwrap_or(self.event.time)
+    }
 }
 
-/// Statistical analysis for simulation results
-pub mod statistics {
-    use super::*;
-    use statrs::statistics::Statistics;
+/// A reached barrier whose models still have events due at or before it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingBarrier {
+    time: f64,
+    model_ids: Vec<String>,
+    /// Later events of the listed models, in the order they were popped
+    held: Vec<ScheduledEvent>,
+}
 
-    pub struct SimulationStatistics {
-        metrics: HashMap<String, Vec<f64>>,
+impl PendingBarrier {
+    /// Whether `scheduled` must run before the barrier releases
+    fn waits_for(&self, scheduled: &ScheduledEvent) -> bool {
+        self.model_ids.contains(&scheduled.event.model_id) && scheduled.due_time() <= self.time
     }
 
-    impl SimulationStatistics {
-        pub fn new(metrics: HashMap<String, Vec<f64>>) -> Self {
-            Self { metrics }
+    /// Whether `scheduled` must wait until the barrier releases
+    fn holds(&self, scheduled: &ScheduledEvent) -> bool {
+        self.model_ids.contains(&scheduled.event.model_id) && scheduled.due_time() > self.time
+    }
+}
+
+// Ordered by time, then by the tie-break policy and finally by insertion
+// sequence, so the queue pops events deterministically
+impl Ord for ScheduledEvent {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let tie = match self.tie_break {
+            TieBreak::Fifo => Ordering::Equal,
+            TieBreak::ByPriority => other.event.priority.cmp(&self.event.priority),
+            TieBreak::ByModelId => self.event.model_id.cmp(&other.event.model_id),
+        };
+        self.event
+            .time
+            .total_cmp(&other.event.time)
+            .then(tie)
+            .then(self.seq.cmp(&other.seq))
+    }
+}
+
+impl PartialOrd for ScheduledEvent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ScheduledEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ScheduledEvent {}
+
+/// Serializable state of a `SimulationEngine` at a point in simulated time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationSnapshot {
+    time: f64,
+    events: Vec<ScheduledEvent>,
+    #[serde(default)]
+    barriers: Vec<PendingBarrier>,
+    next_seq: u64,
+    next_event_id: u64,
+    models: HashMap<String, AIModel>,
+    metrics: HashMap<String, Vec<f64>>,
+    #[serde(default)]
+    streaming_metrics: Option<HashMap<String, statistics::StreamingSummary>>,
+    rng: ChaCha8Rng,
+    #[serde(default)]
+    antithetic: bool,
+    seed: Option<u64>,
+    event_costs: HashMap<EventTypeKind, f64>,
+    arrival_latency: Option<(f64, f64)>,
+    inactive_models: HashSet<String>,
+    past_event_policy: PastEventPolicy,
+    #[serde(default)]
+    tie_break: TieBreak,
+    warmup_until: f64,
+}
+
+impl SimulationSnapshot {
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn pending_events(&self) -> usize {
+        self.events.len() + self.barriers.iter().map(|barrier| barrier.held.len()).sum::<usize>()
+    }
+
+    pub fn metrics(&self) -> &HashMap<String, Vec<f64>> {
+        &self.metrics
+    }
+
+    /// Writes the snapshot to `path` as JSON. Floats round-trip exactly, so a
+    /// loaded snapshot resumes on the same trajectory.
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), anyhow::Error> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, anyhow::Error> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        Ok(serde_json::from_reader(file)?)
+    }
+}
+
+/// A complete, file-describable simulation setup: the models, the events to
+/// pre-schedule, the metrics to track, the seed and the horizon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub seed: Option<u64>,
+    pub end_time: f64,
+    #[serde(default)]
+    pub models: Vec<AIModel>,
+    #[serde(default)]
+    pub events: Vec<Event>,
+    /// Metric names created up front so they appear even if never recorded
+    #[serde(default)]
+    pub metrics: Vec<String>,
+}
+
+impl Scenario {
+    pub fn from_json(json: &str) -> Result<Self, anyhow::Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, anyhow::Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl Default for SimulationEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulationEngine {
+    pub fn new() -> Self {
+        Self {
+            time: 0.0,
+            events: BinaryHeap::new(),
+            barriers: Vec::new(),
+            next_seq: 0,
+            next_event_id: 0,
+            models: HashMap::new(),
+            metrics: HashMap::new(),
+            streaming_metrics: None,
+            rng: SimRng::new(ChaCha8Rng::from_entropy()),
+            seed: None,
+            observers: Vec::new(),
+            handlers: HashMap::new(),
+            evaluators: HashMap::new(),
+            guards: HashMap::new(),
+            sub_simulations: HashMap::new(),
+            depth: 0,
+            declared_metrics: None,
+            undeclared_metrics: BTreeSet::new(),
+            event_costs: HashMap::new(),
+            arrival_latency: None,
+            inactive_models: HashSet::new(),
+            profile: None,
+            trace: None,
+            progress: None,
+            past_event_policy: PastEventPolicy::default(),
+            tie_break: TieBreak::default(),
+            warmup_until: 0.0,
         }
+    }
 
-        pub fn calculate_summary(&self) -> HashMap<String, MetricSummary> {
-            self.metrics
-                .iter()
-                .map(|(name, values)| {
-                    let summary = MetricSummary {
-                        mean: values.mean(),
-                        std_dev: values.std_dev(),
-                        min: values.min(),
-                        max: values.max(),
-                        median: values.median(),
-                    };
-                    (name.clone(), summary)
-                })
-                .collect()
+    /// Creates an engine whose random draws are fully determined by `seed`
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rng: SimRng::new(ChaCha8Rng::seed_from_u64(seed)),
+            seed: Some(seed),
+            ..Self::new()
         }
     }
 
-    #[derive(Debug)]
-    pub struct MetricSummary {
-        pub mean: f64,
-        pub std_dev: f64,
-        pub min: f64,
-        pub max: f64,
-        pub median: f64,
+    /// The seed the RNG was last seeded with, or `None` if it was seeded from
+    /// entropy and the run cannot be reproduced
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
     }
-}
 
-/// Visualization utilities for simulation results
-pub mod visualization {
-    use super::*;
+    /// Restarts the RNG stream from `seed`, e.g. between replications
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng.inner = ChaCha8Rng::seed_from_u64(seed);
+        self.seed = Some(seed);
+    }
 
-    pub struct SimulationVisualizer {
-        metrics: HashMap<String, Vec<f64>>,
+    /// Complements every random draw, turning each uniform `u` into `1 - u`.
+    /// Two engines seeded alike, one of them antithetic, produce negatively
+    /// correlated runs whose average has lower variance. Draws made by
+    /// inverse transform (Weibull arrivals) are mirrored exactly; samplers
+    /// that combine several draws (exponential, normal) are only partially.
+    pub fn set_antithetic(&mut self, antithetic: bool) {
+        self.rng.antithetic = antithetic;
     }
 
-    impl SimulationVisualizer {
-        pub fn new(metrics: HashMap<String, Vec<f64>>) -> Self {
-            Self { metrics }
+    /// Captures the engine state, including the RNG stream position, so the
+    /// run can later continue exactly where it left off. Observers, handlers,
+    /// guards and sub-simulations are not captured and must be registered
+    /// again after `restore`; conditional events whose guard is missing are
+    /// skipped.
+    pub fn checkpoint(&self) -> SimulationSnapshot {
+        SimulationSnapshot {
+            time: self.time,
+            events: self.events.iter().map(|Reverse(scheduled)| scheduled.clone()).collect(),
+            barriers: self.barriers.clone(),
+            next_seq: self.next_seq,
+            next_event_id: self.next_event_id,
+            models: self.models.clone(),
+            metrics: self.metrics.clone(),
+            streaming_metrics: self.streaming_metrics.clone(),
+            rng: self.rng.inner.clone(),
+            antithetic: self.rng.antithetic,
+            seed: self.seed,
+            event_costs: self.event_costs.clone(),
+            arrival_latency: self.arrival_latency,
+            inactive_models: self.inactive_models.clone(),
+            past_event_policy: self.past_event_policy,
+            tie_break: self.tie_break,
+            warmup_until: self.warmup_until,
+        }
+    }
+
+    pub fn restore(snapshot: SimulationSnapshot) -> Self {
+        Self {
+            time: snapshot.time,
+            events: snapshot.events.into_iter().map(Reverse).collect(),
+            barriers: snapshot.barriers,
+            next_seq: snapshot.next_seq,
+            next_event_id: snapshot.next_event_id,
+            models: snapshot.models,
+            metrics: snapshot.metrics,
+            streaming_metrics: snapshot.streaming_metrics,
+            rng: SimRng {
+                inner: snapshot.rng,
+                antithetic: snapshot.antithetic,
+            },
+            seed: snapshot.seed,
+            observers: Vec::new(),
+            handlers: HashMap::new(),
+            evaluators: HashMap::new(),
+            guards: HashMap::new(),
+            sub_simulations: HashMap::new(),
+            depth: 0,
+            declared_metrics: None,
+            undeclared_metrics: BTreeSet::new(),
+            event_costs: snapshot.event_costs,
+            arrival_latency: snapshot.arrival_latency,
+            inactive_models: snapshot.inactive_models,
+            profile: None,
+            trace: None,
+            progress: None,
+            past_event_policy: snapshot.past_event_policy,
+            tie_break: snapshot.tie_break,
+            warmup_until: snapshot.warmup_until,
         }
+    }
 
-        pub fn plot_metric(&self, metric_name: &str) -> Result<(), anyhow::Error> {
-            if let Some(values) = self.metrics.get(metric_name) {
-                // Implement plotting logic
-                // This would typically use a plotting library like plotters
+    /// Builds an engine from a scenario, validating every declared event the
+    /// same way `Event::builder` does and rejecting events for unknown models
+    pub fn from_scenario(scenario: &Scenario) -> Result<SimulationEngine, anyhow::Error> {
+        if !scenario.end_time.is_finite() || scenario.end_time < 0.0 {
+            anyhow::bail!(
+                "scenario end time must be finite and non-negative, got {}",
+                scenario.end_time
+            );
+        }
+        let mut engine = match scenario.seed {
+            Some(seed) => Self::with_seed(seed),
+            None => Self::new(),
+        };
+        for model in &scenario.models {
+            engine.add_model(model.clone());
+        }
+        for name in &scenario.metrics {
+            engine.metrics.entry(name.clone()).or_default();
+        }
+        for event in &scenario.events {
+            if !engine.models.contains_key(&event.model_id) {
+                anyhow::bail!("scenario event targets unknown model '{}'", event.model_id);
+            }
+            let mut builder = Event::builder()
+                .time(event.time)
+                .event_type(event.event_type.clone())
+                .model_id(event.model_id.clone())
+                .priority(event.priority);
+            if let Some(deadline) = event.deadline {
+                builder = builder.deadline(deadline);
+            }
+            engine.schedule_event(builder.build()?)?;
+        }
+        Ok(engine)
+    }
+
+    /// Clears the clock, event queue and metrics for another run, keeping
+    /// models, observers, handlers, sub-simulations, event costs and the RNG
+    /// stream
+    pub fn reset(&mut self) {
+        self.time = 0.0;
+        self.events.clear();
+        self.barriers.clear();
+        self.guards.clear();
+        self.metrics.clear();
+        if let Some(streaming) = &mut self.streaming_metrics {
+            streaming.clear();
+        }
+        self.undeclared_metrics.clear();
+        if let Some(profile) = &mut self.profile {
+            profile.clear();
+        }
+        if let Some(trace) = &mut self.trace {
+            *trace = observer::TraceObserver::new();
+        }
+        if let Some(progress) = &mut self.progress {
+            progress.next = progress.every;
+        }
+    }
+
+    /// Returns the engine to a freshly constructed state apart from the RNG,
+    /// which continues its stream
+    pub fn reset_all(&mut self) {
+        self.reset();
+        self.models.clear();
+        self.observers.clear();
+        self.handlers.clear();
+        self.evaluators.clear();
+        self.sub_simulations.clear();
+        self.declared_metrics = None;
+        self.event_costs.clear();
+        self.arrival_latency = None;
+        self.inactive_models.clear();
+        self.profile = None;
+        self.trace = None;
+        self.progress = None;
+        self.streaming_metrics = None;
+        self.past_event_policy = PastEventPolicy::default();
+        self.tie_break = TieBreak::default();
+        self.warmup_until = 0.0;
+    }
+
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    /// Records a sample, flagging the name if no model declared it. Names are
+    /// checked against the declarations collected at the start of the last
+    /// run, and only when at least one model declares metrics.
+    pub fn record_metric(&mut self, name: &str, value: f64) {
+        if let Some(declared) = &self.declared_metrics {
+            if !declared.contains(name) && self.undeclared_metrics.insert(name.to_string()) {
+                log::warn!("metric '{}' is not declared by any model", name);
             }
-            Ok(())
         }
+        self.push_metric(name, value);
+    }
+
+    /// Every metric recorded so far, in recording order per name
+    pub fn metrics(&self) -> &HashMap<String, Vec<f64>> {
+        &self.metrics
+    }
+
+    /// Switches metrics recorded from now on to streaming mode, where each
+    /// name keeps a `StreamingSummary` instead of every sample. Samples
+    /// already stored stay in `metrics`.
+    pub fn enable_streaming_metrics(&mut self) {
+        self.streaming_metrics.get_or_insert_with(HashMap::new);
+    }
+
+    /// Summaries of the metrics recorded in streaming mode. Empty unless
+    /// streaming is enabled.
+    pub fn streaming_metrics(&self) -> HashMap<String, statistics::StreamingSummary> {
+        self.streaming_metrics.clone().unwrap_or_default()
+    }
 
-        pub fn generate_report(&self) -> Result<String, anyhow::Error> {
-            // Implement report generation logic
-            Ok(String::new())
+    /// Statistics over the recorded metrics, resampling with the engine's
+    /// seed when it has one
+    pub fn statistics(&self) -> statistics::SimulationStatistics {
+        let stats = statistics::SimulationStatistics::new(self.metrics.clone())
+            .with_streaming(self.streaming_metrics());
+        match self.seed {
+            Some(seed) => stats.with_seed(seed),
+            None => stats,
         }
     }
-} 
\ No newline at end of file
+
+    /// Names recorded during a run that no model's simulation config declares
+    pub fn undeclared_metrics(&self) -> &BTreeSet<String> {
+        &self.undeclared_metrics
+    }
+
+    /// Excludes samples recorded before simulation time `t` from the stored
+    /// metrics, so the initial transient doesn't bias summaries. Events still
+    /// run during the warm-up and observers still see its samples.
+    pub fn set_warmup(&mut self, t: f64) -> Result<(), anyhow::Error> {
+        if !t.is_finite() || t < 0.0 {
+            anyhow::bail!("warm-up time must be finite and non-negative, got {}", t);
+        }
+        self.warmup_until = t;
+        Ok(())
+    }
+
+    fn push_metric(&mut self, name: &str, value: f64) {
+        if self.time >= self.warmup_until {
+            match &mut self.streaming_metrics {
+                Some(streaming) => streaming.entry(name.to_string()).or_default().push(value),
+                None => self.metrics.entry(name.to_string()).or_default().push(value),
+            }
+        }
+        for observer in &mut self.observers {
+            observer.on_metric(name, value);
+        }
+    }
+
+    /// Registers the handler for `EventType::Custom(name)` events, replacing
+    /// any previous handler for that name
+    pub fn register_handler(&mut self, name: &str, handler: EventHandler) {
+        self.handlers.insert(name.to_string(), handler);
+    }
+
+    /// Registers the evaluator run for `model_id`'s `Evaluation` events,
+    /// replacing any previous one. Its result is recorded as
+    /// `<model>.accuracy`. The evaluator is dropped along with the model.
+    pub fn register_evaluator(&mut self, model_id: &str, evaluator: Evaluator) {
+        self.evaluators.insert(model_id.to_string(), evaluator);
+    }
+
+    pub fn add_model(&mut self, model: AIModel) {
+        self.models.insert(model.name.clone(), model);
+    }
+
+    pub fn get_model(&self, name: &str) -> Option<&AIModel> {
+        self.models.get(name)
+    }
+
+    /// Removes the model along with its pending events, evaluator and
+    /// sub-simulation, keeping the metrics it already recorded
+    pub fn remove_model(&mut self, name: &str) -> Option<AIModel> {
+        let model = self.models.remove(name)?;
+        self.clear_model_events(name);
+        self.inactive_models.remove(name);
+        self.evaluators.remove(name);
+        self.sub_simulations.remove(name);
+        Some(model)
+    }
+
+    /// Attaches a nested engine to `model_id`, replacing any previous one.
+    /// Fails if the child's own nesting would exceed
+    /// `MAX_SUB_SIMULATION_DEPTH` below the top-level engine.
+    pub fn register_sub_simulation(
+        &mut self,
+        model_id: &str,
+        mut sub: SubSimulation,
+    ) -> Result<(), anyhow::Error> {
+        if !is_positive(sub.horizon) {
+            anyhow::bail!("sub-simulation horizon must be positive, got {}", sub.horizon);
+        }
+        let depth = self.depth + 1 + sub.engine.nesting_height();
+        if depth > MAX_SUB_SIMULATION_DEPTH {
+            anyhow::bail!(
+                "sub-simulation for '{}' would nest {} levels deep, limit is {}",
+                model_id,
+                depth,
+                MAX_SUB_SIMULATION_DEPTH
+            );
+        }
+        sub.engine.set_depth(self.depth + 1);
+        self.sub_simulations.insert(model_id.to_string(), sub);
+        Ok(())
+    }
+
+    pub fn sub_simulation(&self, model_id: &str) -> Option<&SubSimulation> {
+        self.sub_simulations.get(model_id)
+    }
+
+    /// Number of sub-simulation levels below this engine
+    fn nesting_height(&self) -> usize {
+        self.sub_simulations
+            .values()
+            .map(|sub| 1 + sub.engine.nesting_height())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        for sub in self.sub_simulations.values_mut() {
+            sub.engine.set_depth(depth + 1);
+        }
+    }
+
+    pub fn set_past_event_policy(&mut self, policy: PastEventPolicy) {
+        self.past_event_policy = policy;
+    }
+
+    /// Chooses the order of events scheduled for the same time, reordering
+    /// the events already pending
+    pub fn set_tie_break(&mut self, policy: TieBreak) {
+        self.tie_break = policy;
+        let pending = std::mem::take(&mut self.events);
+        self.events = pending
+            .into_iter()
+            .map(|Reverse(mut scheduled)| {
+                scheduled.tie_break = policy;
+                Reverse(scheduled)
+            })
+            .collect();
+    }
+
+    /// Queues `event`. NaN and infinite times are rejected, and an event timed
+    /// before the current clock is rejected or clamped to now according to
+    /// the engine's `PastEventPolicy`.
+    pub fn schedule_event(&mut self, mut event: Event) -> Result<(), RuntimeError> {
+        self.check_event_time(&mut event)?;
+        self.enqueue(ScheduledEvent::new(event));
+        Ok(())
+    }
+
+    /// Schedules `event_type` for `model_id` at `first_time` and then every
+    /// `period` time units after each firing. The returned id cancels all
+    /// future occurrences.
+    pub fn schedule_recurring(
+        &mut self,
+        first_time: f64,
+        period: f64,
+        event_type: EventType,
+        model_id: String,
+    ) -> Result<EventId, anyhow::Error> {
+        if !is_positive(period) {
+            anyhow::bail!("recurring event period must be positive, got {}", period);
+        }
+        let mut event = Event::builder()
+            .time(first_time)
+            .event_type(event_type)
+            .model_id(model_id)
+            .build()?;
+        self.check_event_time(&mut event)?;
+
+        let id = EventId(self.next_event_id);
+        self.next_event_id += 1;
+        self.enqueue(ScheduledEvent {
+            period: Some(period),
+            id: Some(id),
+            ..ScheduledEvent::new(event)
+        });
+        Ok(id)
+    }
+
+    /// Schedules an event that only runs if `guard` accepts the target model
+    /// when it comes due. A rejected event is dropped without being processed,
+    /// though the clock still advances to its time. The guard must not mutate
+    /// state.
+    pub fn schedule_conditional(
+        &mut self,
+        time: f64,
+        event_type: EventType,
+        model_id: String,
+        guard: EventGuard,
+    ) -> Result<EventId, anyhow::Error> {
+        let mut event = Event::builder()
+            .time(time)
+            .event_type(event_type)
+            .model_id(model_id)
+            .build()?;
+        self.check_event_time(&mut event)?;
+
+        let id = EventId(self.next_event_id);
+        self.next_event_id += 1;
+        self.guards.insert(id, guard);
+        self.enqueue(ScheduledEvent {
+            id: Some(id),
+            conditional: true,
+            ..ScheduledEvent::new(event)
+        });
+        Ok(id)
+    }
+
+    /// Synchronizes `model_ids` at `time`: once the clock reaches the barrier,
+    /// none of those models processes an event due after `time` until every
+    /// one of them has processed all its events due at or before `time`,
+    /// including arrivals the arrival latency delivers past the barrier.
+    /// Later events of the listed models are held while the barrier waits and
+    /// then run in their original order as soon as the clock allows. Events
+    /// of unlisted models are unaffected.
+    pub fn schedule_barrier(&mut self, time: f64, model_ids: Vec<String>) -> Result<(), anyhow::Error> {
+        if let Some(unknown) = model_ids.iter().find(|id| !self.models.contains_key(*id)) {
+            anyhow::bail!("barrier lists unknown model '{}'", unknown);
+        }
+        let mut event = Event {
+            time,
+            event_type: EventType::Barrier(model_ids),
+            model_id: String::new(),
+            priority: 0,
+            deadline: None,
+        };
+        self.check_event_time(&mut event)?;
+        self.enqueue(ScheduledEvent::new(event));
+        Ok(())
+    }
+
+    /// Re-queues the held events of every reached barrier whose models have
+    /// no events due at or before it left, queued or held by another barrier
+    fn release_barriers(&mut self) {
+        if self.barriers.is_empty() {
+            return;
+        }
+        let pending: Vec<&ScheduledEvent> = self
+            .events
+            .iter()
+            .map(|Reverse(scheduled)| scheduled)
+            .chain(self.barriers.iter().flat_map(|barrier| &barrier.held))
+            .collect();
+        let drained: Vec<bool> = self
+            .barriers
+            .iter()
+            .map(|barrier| !pending.iter().any(|scheduled| barrier.waits_for(scheduled)))
+            .collect();
+        for (barrier, drained) in std::mem::take(&mut self.barriers).into_iter().zip(drained) {
+            if drained {
+                for scheduled in barrier.held {
+                    self.enqueue(scheduled);
+                }
+            } else {
+                self.barriers.push(barrier);
+            }
+        }
+    }
+
+    /// Stops a recurring series or drops a conditional event, returning
+    /// whether it was still pending
+    pub fn cancel(&mut self, id: EventId) -> bool {
+        self.guards.remove(&id);
+        let before = self.pending_events();
+        self.events
+            .retain(|Reverse(scheduled)| scheduled.id != Some(id));
+        for barrier in &mut self.barriers {
+            barrier.held.retain(|scheduled| scheduled.id != Some(id));
+        }
+        let cancelled = before != self.pending_events();
+        self.release_barriers();
+        cancelled
+    }
+
+    /// Queued events, including those held by a barrier
+    pub fn pending_events(&self) -> usize {
+        self.events.len() + self.barriers.iter().map(|barrier| barrier.held.len()).sum::<usize>()
+    }
+
+    /// Rejects non-finite times and applies the `PastEventPolicy` to times
+    /// before the current clock
+    fn check_event_time(&self, event: &mut Event) -> Result<(), RuntimeError> {
+        if !event.time.is_finite() {
+            return Err(RuntimeError::Time(format!(
+                "cannot schedule event for '{}' at non-finite time {}",
+                event.model_id, event.time
+            )));
+        }
+        if event.time < self.time {
+            match self.past_event_policy {
+                PastEventPolicy::Reject => {
+                    return Err(RuntimeError::Time(format!(
+                        "cannot schedule event for '{}' at time {} before current time {}",
+                        event.model_id, event.time, self.time
+                    )));
+                }
+                PastEventPolicy::ClampToNow => event.time = self.time,
+            }
+        }
+        Ok(())
+    }
+
+    /// Schedules a stream of events whose inter-arrival times are drawn from
+    /// `dist`; each firing automatically schedules the next arrival
+    pub fn schedule_stochastic(
+        &mut self,
+        event_type: EventType,
+        model_id: String,
+        dist: ArrivalDist,
+    ) -> Result<(), anyhow::Error> {
+        dist.validate()?;
+        let time = self.time + dist.sample(&mut self.rng);
+        self.enqueue(ScheduledEvent {
+            arrival: Some(dist),
+            ..ScheduledEvent::new(Event {
+                time,
+                event_type,
+                model_id,
+                priority: 0,
+                deadline: None,
+            })
+        });
+        Ok(())
+    }
+
+    /// Stops `model_id` from receiving events while keeping the model, its
+    /// pending events and its metrics. Its events are skipped as they come
+    /// due; stochastic streams keep drawing arrivals so they resume on
+    /// `reactivate_model`.
+    pub fn deactivate_model(&mut self, model_id: &str) -> Result<(), anyhow::Error> {
+        if !self.models.contains_key(model_id) {
+            anyhow::bail!("unknown model '{}'", model_id);
+        }
+        self.inactive_models.insert(model_id.to_string());
+        Ok(())
+    }
+
+    pub fn reactivate_model(&mut self, model_id: &str) -> Result<(), anyhow::Error> {
+        if !self.models.contains_key(model_id) {
+            anyhow::bail!("unknown model '{}'", model_id);
+        }
+        self.inactive_models.remove(model_id);
+        Ok(())
+    }
+
+    pub fn is_active(&self, model_id: &str) -> bool {
+        self.models.contains_key(model_id) && !self.inactive_models.contains(model_id)
+    }
+
+    /// Drops every pending event for `model_id`, including stochastic arrival
+    /// streams, while keeping the model and its recorded metrics. Returns the
+    /// number of events removed.
+    pub fn clear_model_events(&mut self, model_id: &str) -> usize {
+        let before = self.pending_events();
+        self.events
+            .retain(|Reverse(scheduled)| scheduled.event.model_id != model_id);
+        for barrier in &mut self.barriers {
+            barrier.held.retain(|scheduled| scheduled.event.model_id != model_id);
+        }
+        let pending: HashSet<EventId> = self
+            .events
+            .iter()
+            .map(|Reverse(scheduled)| scheduled)
+            .chain(self.barriers.iter().flat_map(|barrier| &barrier.held))
+            .filter_map(|scheduled| scheduled.id)
+            .collect();
+        self.guards.retain(|id, _| pending.contains(id));
+        let removed = before - self.pending_events();
+        self.release_barriers();
+        removed
+    }
+
+    /// Sets the simulated processing time of every event of `kind`. Processing
+    /// such an event advances the clock by `cost`, and events scheduled while
+    /// the engine is busy start once it is free. The default cost is zero.
+    pub fn set_event_cost(&mut self, kind: EventTypeKind, cost: f64) -> Result<(), anyhow::Error> {
+        if !cost.is_finite() || cost < 0.0 {
+            anyhow::bail!("event cost must be finite and non-negative, got {}", cost);
+        }
+        self.event_costs.insert(kind, cost);
+        Ok(())
+    }
+
+    /// Delays the effect of every `DataArrival` by a latency drawn from
+    /// `Normal(mean, jitter_std)` and clamped at zero. Zero mean and jitter
+    /// turn the latency off.
+    pub fn set_arrival_latency(&mut self, mean: f64, jitter_std: f64) -> Result<(), anyhow::Error> {
+        if !mean.is_finite() || mean < 0.0 || !jitter_std.is_finite() || jitter_std < 0.0 {
+            anyhow::bail!(
+                "arrival latency mean and jitter must be finite and non-negative, got mean {} and jitter {}",
+                mean,
+                jitter_std
+            );
+        }
+        self.arrival_latency = (mean > 0.0 || jitter_std > 0.0).then_some((mean, jitter_std));
+        Ok(())
+    }
+
+    /// Starts accumulating the wall-clock time spent processing each event
+    /// kind, with custom events attributed to their handler name. When
+    /// profiling is off the run loop only pays for an `Option` check.
+    pub fn enable_profiling(&mut self) {
+        self.profile.get_or_insert_with(HashMap::new);
+    }
+
+    /// Cumulative processing time per event kind, keyed `ModelUpdate`,
+    /// `DataArrival`, `TrainingStep`, `Evaluation` or `Custom(<name>)`. Empty
+    /// unless profiling is enabled.
+    pub fn profile_report(&self) -> HashMap<String, Duration> {
+        self.profile.clone().unwrap_or_default()
+    }
+
+    /// Starts recording every processed event in firing order. Skipped
+    /// events (inactive models, rejected guards, delayed arrivals) are not
+    /// recorded until they actually run.
+    pub fn enable_trace(&mut self) {
+        self.trace.get_or_insert_with(observer::TraceObserver::new);
+    }
+
+    /// Events processed since tracing was enabled. Empty unless tracing is
+    /// enabled.
+    pub fn trace(&self) -> &[observer::TraceEntry] {
+        self.trace.as_ref().map_or(&[], |trace| trace.entries())
+    }
+
+    /// Calls `callback` with the current time whenever processing an event
+    /// carries the clock past a multiple of `every`. Boundaries crossed by a
+    /// single jump of the clock are reported once, and the callback is only
+    /// ever called while events are being processed.
+    pub fn set_progress_callback(
+        &mut self,
+        every: f64,
+        callback: ProgressCallback,
+    ) -> Result<(), anyhow::Error> {
+        if !is_positive(every) {
+            anyhow::bail!("progress interval must be positive, got {}", every);
+        }
+        let mut progress = Progress {
+            every,
+            next: 0.0,
+            callback,
+        };
+        progress.next = progress.boundary_after(self.time);
+        self.progress = Some(progress);
+        Ok(())
+    }
+
+    fn report_progress(&mut self) {
+        if let Some(progress) = &mut self.progress {
+            if self.time >= progress.next {
+                (progress.callback)(self.time);
+                progress.next = progress.boundary_after(self.time);
+            }
+        }
+    }
+
+    fn profile_key(event_type: &EventType) -> String {
+        match event_type {
+            EventType::Custom(name) => format!("Custom({})", name),
+            other => format!("{:?}", other.kind()),
+        }
+    }
+
+    fn event_cost(&self, event_type: &EventType) -> f64 {
+        self.event_costs.get(&event_type.kind()).copied().unwrap_or(0.0)
+    }
+
+    fn enqueue(&mut self, mut scheduled: ScheduledEvent) {
+        scheduled.seq = self.next_seq;
+        scheduled.tie_break = self.tie_break;
+        self.next_seq += 1;
+        self.events.push(Reverse(scheduled));
+    }
+
+    pub async fn run(&mut self, end_time: f64) -> Result<(), anyhow::Error> {
+        self.run_events(end_time)
+    }
+
+    /// Synchronous core of `run`, also used to drive sub-simulations from
+    /// inside event processing
+    fn run_events(&mut self, end_time: f64) -> Result<(), anyhow::Error> {
+        self.collect_declared_metrics();
+
+        let mut events_processed = 0;
+        while self.events.peek().is_some_and(|Reverse(next)| next.event.time <= end_time) {
+            if self.fire_next()? {
+                events_processed += 1;
+            }
+        }
+
+        let summary = RunSummary {
+            end_time: self.time,
+            events_processed,
+            pending_events: self.pending_events(),
+        };
+        for observer in &mut self.observers {
+            observer.on_run_end(&summary);
+        }
+        Ok(())
+    }
+
+    /// Processes every event due at or before `t`, like `run`, without
+    /// requiring an async context
+    pub fn run_until(&mut self, t: f64) -> Result<(), anyhow::Error> {
+        self.run_events(t)
+    }
+
+    /// Takes exactly the next event off the queue and returns the clock
+    /// afterwards, or `None` without doing anything if the queue is empty.
+    /// An event that would be skipped during a run (inactive model, rejected
+    /// guard, delayed arrival) is consumed without being processed.
+    pub fn step(&mut self) -> Result<Option<f64>, anyhow::Error> {
+        if self.events.is_empty() {
+            return Ok(None);
+        }
+        self.collect_declared_metrics();
+        self.fire_next()?;
+        Ok(Some(self.time))
+    }
+
+    fn collect_declared_metrics(&mut self) {
+        let declared: HashSet<String> = self
+            .models
+            .values()
+            .flat_map(|model| model.declared_metrics())
+            .collect();
+        self.declared_metrics = (!declared.is_empty()).then_some(declared);
+    }
+
+    /// Pops the next event and runs it, returning whether it was processed
+    /// rather than skipped or deferred
+    fn fire_next(&mut self) -> Result<bool, anyhow::Error> {
+        let processed = match self.events.pop() {
+            Some(Reverse(scheduled)) => self.fire(scheduled)?,
+            None => false,
+        };
+        self.release_barriers();
+        self.report_progress();
+        Ok(processed)
+    }
+
+    fn fire(&mut self, scheduled: ScheduledEvent) -> Result<bool, anyhow::Error> {
+        if let Some(barrier) = self.barriers.iter_mut().find(|barrier| barrier.holds(&scheduled)) {
+            barrier.held.push(scheduled);
+            return Ok(false);
+        }
+        // An event that arrives while a costed event is still being
+        // processed waits until the engine is free
+        self.time = self.time.max(scheduled.event.time);
+        if let Some(dist) = scheduled.arrival {
+            let mut next = scheduled.clone();
+            next.event.time = scheduled.event.time + dist.sample(&mut self.rng);
+            self.enqueue(next);
+        }
+        if let Some(period) = scheduled.period {
+            let mut next = scheduled.clone();
+            next.event.time = scheduled.event.time + period;
+            self.enqueue(next);
+        }
+        if let EventType::Barrier(model_ids) = scheduled.event.event_type {
+            self.barriers.push(PendingBarrier {
+                time: scheduled.event.time,
+                model_ids,
+                held: Vec::new(),
+            });
+            return Ok(false);
+        }
+        if self.inactive_models.contains(&scheduled.event.model_id) {
+            if let (true, Some(id)) = (scheduled.conditional, scheduled.id) {
+                self.guards.remove(&id);
+            }
+            return Ok(false);
+        }
+        if let Some((mean, jitter_std)) = self.arrival_latency {
+            if !scheduled.delayed && scheduled.event.event_type.kind() == EventTypeKind::DataArrival {
+                let latency = Normal::new(mean, jitter_std).unwrap().sample(&mut self.rng).max(0.0);
+                let mut event = scheduled.event;
+                let due = event.time;
+                event.time += latency;
+                self.enqueue(ScheduledEvent {
+                    delayed: true,
+                    due: Some(due),
+                    id: scheduled.id,
+                    conditional: scheduled.conditional,
+                    ..ScheduledEvent::new(event)
+                });
+                return Ok(false);
+            }
+        }
+        if let (true, Some(id)) = (scheduled.conditional, scheduled.id) {
+            let accepted = match self.guards.remove(&id) {
+                Some(guard) => self
+                    .models
+                    .get(&scheduled.event.model_id)
+                    .is_some_and(guard),
+                None => {
+                    log::warn!(
+                        "skipping conditional event for '{}' whose guard is not registered",
+                        scheduled.event.model_id
+                    );
+                    false
+                }
+            };
+            if !accepted {
+                return Ok(false);
+            }
+        }
+        for observer in &mut self.observers {
+            observer.on_event(&scheduled.event, self.time);
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.on_event(&scheduled.event, self.time);
+        }
+        let cost = self.event_cost(&scheduled.event.event_type);
+        let profiled = self
+            .profile
+            .is_some()
+            .then(|| (Self::profile_key(&scheduled.event.event_type), Instant::now()));
+        self.process_event(scheduled.event)?;
+        if let (Some((key, started)), Some(profile)) = (profiled, &mut self.profile) {
+            *profile.entry(key).or_default() += started.elapsed();
+        }
+        self.time += cost;
+        Ok(true)
+    }
+
+    /// Jumps the clock to the next scheduled event's time without processing
+    /// it, so callers can account for the idle interval. Returns the new time,
+    /// or `None` when no events are pending.
+    pub fn advance_to_next_event(&mut self) -> Option<f64> {
+        let Reverse(next) = self.events.peek()?;
+        let next = next.event.time;
+        self.time = self.time.max(next);
+        Some(self.time)
+    }
+
+    /// Runs like `run`, additionally pushing every recorded metric to the
+    /// returned receiver as it happens. The channel holds
+    /// `METRIC_STREAM_CAPACITY` samples; recording never blocks the run, so
+    /// samples arriving while it is full are dropped with a warning. Dropping
+    /// the receiver early is harmless.
+    pub fn run_with_stream(
+        &mut self,
+        end_time: f64,
+    ) -> (
+        impl Future<Output = Result<(), anyhow::Error>> + '_,
+        mpsc::Receiver<(String, f64)>,
+    ) {
+        let (sender, receiver) = mpsc::channel(METRIC_STREAM_CAPACITY);
+        let run = async move {
+            self.observers.push(Box::new(observer::StreamObserver::new(sender)));
+            let result = self.run(end_time).await;
+            self.observers.pop();
+            result
+        };
+        (run, receiver)
+    }
+
+    fn process_event(&mut self, event: Event) -> Result<(), anyhow::Error> {
+        // The model is taken out of the map while its handler runs so the
+        // handler can borrow the engine mutably
+        if let Some(mut model) = self.models.remove(&event.model_id) {
+            let result = match &event.event_type {
+                EventType::ModelUpdate => self.update_model(&mut model),
+                EventType::DataArrival => self.process_data(&mut model),
+                EventType::TrainingStep => self.train_model(&mut model),
+                EventType::Evaluation => self.evaluate_model(&mut model),
+                EventType::Custom(name) => match self.handlers.get_mut(name) {
+                    Some(handler) => {
+                        let mut sink = MetricSink::default();
+                        let result = handler(&mut model, self.time, &mut sink);
+                        for (metric, value) in sink.samples {
+                            self.record_metric(&metric, value);
+                        }
+                        result
+                    }
+                    None => Err(anyhow::anyhow!(
+                        "no handler registered for custom event '{}' on model '{}'",
+                        name,
+                        event.model_id
+                    )),
+                },
+                // Barriers are resolved by the run loop before dispatch
+                EventType::Barrier(_) => Ok(()),
+            };
+            self.models.insert(event.model_id.clone(), model);
+            result?;
+        }
+        self.run_sub_simulation(&event)
+    }
+
+    /// Runs the child engine registered for the event's model, if the event is
+    /// its trigger, and lifts the samples it recorded into this engine
+    fn run_sub_simulation(&mut self, event: &Event) -> Result<(), anyhow::Error> {
+        let Some(mut sub) = self.sub_simulations.remove(&event.model_id) else {
+            return Ok(());
+        };
+        if sub.trigger != event.event_type {
+            self.sub_simulations.insert(event.model_id.clone(), sub);
+            return Ok(());
+        }
+        if sub.engine.depth > MAX_SUB_SIMULATION_DEPTH {
+            self.sub_simulations.insert(event.model_id.clone(), sub);
+            anyhow::bail!(
+                "sub-simulation for '{}' exceeds the depth limit of {}",
+                event.model_id,
+                MAX_SUB_SIMULATION_DEPTH
+            );
+        }
+
+        let recorded: Vec<usize> = sub
+            .lifted_metrics
+            .iter()
+            .map(|metric| sub.engine.metrics.get(metric).map_or(0, Vec::len))
+            .collect();
+        let end_time = sub.engine.time + sub.horizon;
+        let result = sub.engine.run_events(end_time);
+        // Idle children still cover the whole horizon so the next trigger
+        // continues from where this one ended
+        sub.engine.time = sub.engine.time.max(end_time);
+
+        if result.is_ok() {
+            for (metric, &start) in sub.lifted_metrics.iter().zip(&recorded) {
+                let name = format!("{}.{}", event.model_id, metric);
+                let values = sub.engine.metrics.get(metric).map_or(&[][..], |v| &v[start..]);
+                // Lifted names are namespaced by model id and so are not
+                // checked against declarations
+                for &value in values {
+                    self.push_metric(&name, value);
+                }
+            }
+        }
+        self.sub_simulations.insert(event.model_id.clone(), sub);
+        result
+    }
+
+    // The built-in handlers record engine-generated metrics namespaced by
+    // model id, which bypass the declared-metrics check
+
+    fn update_model(&mut self, model: &mut AIModel) -> Result<(), anyhow::Error> {
+        self.push_metric(&format!("{}.parameter_norm", model.name), model.parameter_norm());
+        Ok(())
+    }
+
+    fn process_data(&mut self, model: &mut AIModel) -> Result<(), anyhow::Error> {
+        self.push_metric(&format!("{}.data_arrivals", model.name), 1.0);
+        Ok(())
+    }
+
+    fn train_model(&mut self, model: &mut AIModel) -> Result<(), anyhow::Error> {
+        self.push_metric(&format!("{}.training_steps", model.name), 1.0);
+        Ok(())
+    }
+
+    // Models without an evaluator only count their evaluations
+    fn evaluate_model(&mut self, model: &mut AIModel) -> Result<(), anyhow::Error> {
+        match self.evaluators.get_mut(&model.name) {
+            Some(evaluator) => {
+                let accuracy = evaluator(model)?;
+                if !(0.0..=1.0).contains(&accuracy) {
+                    anyhow::bail!(
+                        "evaluator for '{}' returned accuracy {} outside [0, 1]",
+                        model.name,
+                        accuracy
+                    );
+                }
+                self.push_metric(&format!("{}.accuracy", model.name), accuracy);
+            }
+            None => self.push_metric(&format!("{}.evaluations", model.name), 1.0),
+        }
+        Ok(())
+    }
+}
+
+/// Pluggable instrumentation of simulation runs
+pub mod observer {
+    use super::*;
+    use std::io::Write;
+
+    /// Receives callbacks as the engine fires events and records metrics
+    pub trait Observer: Send {
+        /// Called just before `event` is processed at simulation time `time`
+        fn on_event(&mut self, event: &Event, time: f64);
+
+        fn on_metric(&mut self, name: &str, value: f64);
+
+        fn on_run_end(&mut self, summary: &RunSummary);
+    }
+
+    /// Outcome of a single call to `SimulationEngine::run`
+    #[derive(Debug, Clone)]
+    pub struct RunSummary {
+        pub end_time: f64,
+        pub events_processed: usize,
+        pub pending_events: usize,
+    }
+
+    /// A fired event as seen by a `TraceObserver` or recorded by
+    /// `SimulationEngine::enable_trace`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct TraceEntry {
+        pub time: f64,
+        pub model_id: String,
+        pub event_type: String,
+    }
+
+    /// Collects every fired event in firing order
+    #[derive(Debug, Default)]
+    pub struct TraceObserver {
+        entries: Vec<TraceEntry>,
+    }
+
+    impl TraceObserver {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn entries(&self) -> &[TraceEntry] {
+            &self.entries
+        }
+    }
+
+    impl Observer for TraceObserver {
+        fn on_event(&mut self, event: &Event, time: f64) {
+            self.entries.push(TraceEntry {
+                time,
+                model_id: event.model_id.clone(),
+                event_type: format!("{:?}", event.event_type),
+            });
+        }
+
+        fn on_metric(&mut self, _name: &str, _value: f64) {}
+
+        fn on_run_end(&mut self, _summary: &RunSummary) {}
+    }
+
+    /// Writes every recorded metric as a `time,metric,value` CSV row, stamped
+    /// with the time of the event being processed when it was recorded
+    pub struct CsvObserver<W: Write + Send> {
+        writer: W,
+        time: f64,
+        error: Option<std::io::Error>,
+    }
+
+    impl<W: Write + Send> CsvObserver<W> {
+        pub fn new(mut writer: W) -> Self {
+            let error = writeln!(writer, "time,metric,value").err();
+            Self {
+                writer,
+                time: 0.0,
+                error,
+            }
+        }
+
+        /// Flushes the writer and returns it, or the first IO error encountered
+        pub fn finish(mut self) -> std::io::Result<W> {
+            if let Some(error) = self.error {
+                return Err(error);
+            }
+            self.writer.flush()?;
+            Ok(self.writer)
+        }
+    }
+
+    impl<W: Write + Send> Observer for CsvObserver<W> {
+        fn on_event(&mut self, _event: &Event, time: f64) {
+            self.time = time;
+        }
+
+        fn on_metric(&mut self, name: &str, value: f64) {
+            if self.error.is_none() {
+                self.error = writeln!(self.writer, "{},{},{}", self.time, name, value).err();
+            }
+        }
+
+        fn on_run_end(&mut self, _summary: &RunSummary) {
+            if self.error.is_none() {
+                self.error = self.writer.flush().err();
+            }
+        }
+    }
+
+    /// Forwards recorded metrics to a bounded channel, ignoring a closed
+    /// receiver and dropping samples while it is full
+    pub(crate) struct StreamObserver {
+        sender: mpsc::Sender<(String, f64)>,
+        dropped: usize,
+    }
+
+    impl StreamObserver {
+        pub(crate) fn new(sender: mpsc::Sender<(String, f64)>) -> Self {
+            Self { sender, dropped: 0 }
+        }
+    }
+
+    impl Observer for StreamObserver {
+        fn on_event(&mut self, _event: &Event, _time: f64) {}
+
+        fn on_metric(&mut self, name: &str, value: f64) {
+            if let Err(mpsc::error::TrySendError::Full(_)) = self.sender.try_send((name.to_string(), value)) {
+                self.dropped += 1;
+            }
+        }
+
+        fn on_run_end(&mut self, _summary: &RunSummary) {
+            if self.dropped > 0 {
+                log::warn!("metric stream full, dropped {} samples", self.dropped);
+            }
+        }
+    }
+}
+
+/// Parallel simulation capabilities
+pub mod parallel {
+    use super::*;
+
+    pub struct ParallelSimulation {
+        engines: Vec<SimulationEngine>,
+        num_workers: usize,
+        base_seed: Option<u64>,
+    }
+
+    /// Serializable state of every worker in a `ParallelSimulation`
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ParallelSnapshot {
+        base_seed: Option<u64>,
+        workers: Vec<SimulationSnapshot>,
+    }
+
+    impl ParallelSnapshot {
+        pub fn base_seed(&self) -> Option<u64> {
+            self.base_seed
+        }
+
+        pub fn workers(&self) -> &[SimulationSnapshot] {
+            &self.workers
+        }
+    }
+
+    impl ParallelSimulation {
+        pub fn new(num_workers: usize) -> Self {
+            Self {
+                engines: (0..num_workers).map(|_| SimulationEngine::new()).collect(),
+                num_workers,
+                base_seed: None,
+            }
+        }
+
+        /// Creates one worker per seed, in order, for independent replications
+        pub fn with_seeds(seeds: Vec<u64>) -> Result<Self, anyhow::Error> {
+            if seeds.is_empty() {
+                anyhow::bail!("parallel simulation needs at least one seed");
+            }
+            Ok(Self {
+                num_workers: seeds.len(),
+                engines: seeds.into_iter().map(SimulationEngine::with_seed).collect(),
+                base_seed: None,
+            })
+        }
+
+        /// Runs each pre-configured engine as its own worker, so a parameter
+        /// sweep can give workers distinct models, seeds and scheduled events.
+        /// The number of workers is `engines.len()`.
+        pub fn from_engines(engines: Vec<SimulationEngine>) -> Result<Self, anyhow::Error> {
+            if engines.is_empty() {
+                anyhow::bail!("parallel simulation needs at least one engine");
+            }
+            Ok(Self {
+                num_workers: engines.len(),
+                engines,
+                base_seed: None,
+            })
+        }
+
+        /// Creates `2 * pairs` workers for antithetic variates: workers `2k`
+        /// and `2k + 1` share the seed `base_seed + k` and worker `2k + 1`
+        /// draws the antithetic stream of worker `2k`, so averaging each pair
+        /// reduces the variance of the estimate
+        pub fn with_antithetic(pairs: usize, base_seed: u64) -> Result<Self, anyhow::Error> {
+            if pairs == 0 {
+                anyhow::bail!("antithetic simulation needs at least one pair");
+            }
+            let engines = (0..pairs)
+                .flat_map(|k| {
+                    let seed = base_seed.wrapping_add(k as u64);
+                    let mut mirrored = SimulationEngine::with_seed(seed);
+                    mirrored.set_antithetic(true);
+                    [SimulationEngine::with_seed(seed), mirrored]
+                })
+                .collect::<Vec<_>>();
+            Ok(Self {
+                num_workers: engines.len(),
+                engines,
+                base_seed: Some(base_seed),
+            })
+        }
+
+        /// Workers still held, which drops below the starting count once a
+        /// worker panics during `run_parallel`
+        pub fn num_workers(&self) -> usize {
+            self.num_workers
+        }
+
+        /// The worker engines, for configuring models and events before a run
+        pub fn engines_mut(&mut self) -> &mut [SimulationEngine] {
+            &mut self.engines
+        }
+
+        /// Creates `num_workers` engines where worker `i` is seeded with
+        /// `base_seed + i`, making the whole parallel run reproducible
+        pub fn with_base_seed(num_workers: usize, base_seed: u64) -> Self {
+            Self {
+                engines: (0..num_workers)
+                    .map(|i| SimulationEngine::with_seed(base_seed.wrapping_add(i as u64)))
+                    .collect(),
+                num_workers,
+                base_seed: Some(base_seed),
+            }
+        }
+
+        /// Captures every worker's state so a long run can be resumed after a
+        /// restart and continue deterministically.
+        ///
+        /// Only engine state is captured: handlers and other callbacks
+        /// registered on the workers must be registered again after `restore`.
+        pub fn checkpoint(&self) -> ParallelSnapshot {
+            ParallelSnapshot {
+                base_seed: self.base_seed,
+                workers: self.engines.iter().map(SimulationEngine::checkpoint).collect(),
+            }
+        }
+
+        pub fn restore(snapshot: ParallelSnapshot) -> Self {
+            let engines: Vec<SimulationEngine> = snapshot
+                .workers
+                .into_iter()
+                .map(SimulationEngine::restore)
+                .collect();
+            Self {
+                num_workers: engines.len(),
+                engines,
+                base_seed: snapshot.base_seed,
+            }
+        }
+
+        /// Runs every worker to `end_time` on its own task. Each task owns its
+        /// engine for the duration of the run and hands it back afterwards, so
+        /// `aggregate_results` sees the updated state. Returns the first error
+        /// in worker order. A worker whose task panics takes its engine with
+        /// it: the survivors keep their order and `num_workers` shrinks to
+        /// match.
+        pub async fn run_parallel(&mut self, end_time: f64) -> Result<(), anyhow::Error> {
+            let mut handles = Vec::new();
+            
+            for mut engine in std::mem::take(&mut self.engines) {
+                let handle = tokio::spawn(async move {
+                    let result = engine.run(end_time).await;
+                    (engine, result)
+                });
+                handles.push(handle);
+            }
+
+            let mut first_error = None;
+            for (worker, handle) in handles.into_iter().enumerate() {
+                match handle.await {
+                    Ok((engine, result)) => {
+                        self.engines.push(engine);
+                        if let Err(error) = result {
+                            first_error.get_or_insert(error);
+                        }
+                    }
+                    Err(error) => {
+                        first_error.get_or_insert(anyhow::anyhow!("worker {} failed: {}", worker, error));
+                    }
+                }
+            }
+            self.num_workers = self.engines.len();
+
+            match first_error {
+                Some(error) => Err(error),
+                None => Ok(()),
+            }
+        }
+
+        /// Concatenates each metric's stored samples across workers in worker
+        /// index order, so repeated aggregations of the same run produce
+        /// identical vectors. Metrics recorded in streaming mode have no
+        /// samples; see `aggregate_streaming_results`.
+        pub fn aggregate_results(&self) -> HashMap<String, Vec<f64>> {
+            let mut aggregated = HashMap::new();
+            
+            for engine in &self.engines {
+                for (metric, values) in &engine.metrics {
+                    aggregated
+                        .entry(metric.clone())
+                        .or_insert_with(Vec::new)
+                        .extend(values);
+                }
+            }
+
+            aggregated
+        }
+
+        /// `aggregate_results` keyed in sorted metric order
+        pub fn aggregate_results_sorted(&self) -> BTreeMap<String, Vec<f64>> {
+            self.aggregate_results().into_iter().collect()
+        }
+
+        /// Merges each metric's streaming summary across workers, in worker
+        /// index order
+        pub fn aggregate_streaming_results(&self) -> HashMap<String, statistics::StreamingSummary> {
+            let mut aggregated: HashMap<String, statistics::StreamingSummary> = HashMap::new();
+            for engine in &self.engines {
+                for (metric, summary) in engine.streaming_metrics.iter().flatten() {
+                    aggregated.entry(metric.clone()).or_default().merge(summary);
+                }
+            }
+            aggregated
+        }
+
+        /// Statistics over the aggregated samples and streaming summaries of
+        /// every worker
+        pub fn aggregate_statistics(&self) -> statistics::SimulationStatistics {
+            statistics::SimulationStatistics::new(self.aggregate_results())
+                .with_streaming(self.aggregate_streaming_results())
+        }
+
+        /// Serializes the same map as `aggregate_results` to pretty JSON with
+        /// keys in sorted order. Samples are streamed from the workers rather
+        /// than concatenated first. Non-finite samples serialize as `null`.
+        pub fn aggregate_results_json(&self) -> Result<String, anyhow::Error> {
+            Ok(serde_json::to_string_pretty(&AggregateView {
+                engines: &self.engines,
+            })?)
+        }
+    }
+
+    struct AggregateView<'a> {
+        engines: &'a [SimulationEngine],
+    }
+
+    impl Serialize for AggregateView<'_> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let names: BTreeSet<&String> = self
+                .engines
+                .iter()
+                .flat_map(|engine| engine.metrics.keys())
+                .collect();
+            serializer.collect_map(names.into_iter().map(|name| {
+                (name, AggregateSamples { name, engines: self.engines })
+            }))
+        }
+    }
+
+    struct AggregateSamples<'a> {
+        name: &'a str,
+        engines: &'a [SimulationEngine],
+    }
+
+    impl Serialize for AggregateSamples<'_> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(
+                self.engines
+                    .iter()
+                    .filter_map(|engine| engine.metrics.get(self.name))
+                    .flatten(),
+            )
+        }
+    }
+}
+
+/// Statistical analysis for simulation results
+pub mod statistics {
+    use super::*;
+    use statrs::statistics::Statistics;
+
+    /// Sample count above which `calculate_summary` switches to compensated
+    /// summation for the mean
+    pub const KAHAN_MEAN_THRESHOLD: usize = 10_000;
+
+    /// Mean computed with Kahan–Babuška (Neumaier) compensated summation, so
+    /// long runs don't accumulate rounding error. Returns NaN when empty.
+    pub fn kahan_mean(values: &[f64]) -> f64 {
+        if values.is_empty() {
+            return f64::NAN;
+        }
+        let mut sum = 0.0;
+        let mut compensation = 0.0;
+        for &value in values {
+            let total = sum + value;
+            if sum.abs() >= value.abs() {
+                compensation += (sum - total) + value;
+            } else {
+                compensation += (value - total) + sum;
+            }
+            sum = total;
+        }
+        (sum + compensation) / values.len() as f64
+    }
+
+    /// Running count, mean, variance, min and max of a metric, updated with
+    /// Welford's algorithm so samples don't need to be stored
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub struct StreamingSummary {
+        count: u64,
+        mean: f64,
+        m2: f64,
+        min: f64,
+        max: f64,
+    }
+
+    impl Default for StreamingSummary {
+        fn default() -> Self {
+            Self {
+                count: 0,
+                mean: 0.0,
+                m2: 0.0,
+                min: f64::INFINITY,
+                max: f64::NEG_INFINITY,
+            }
+        }
+    }
+
+    impl StreamingSummary {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn from_samples(values: &[f64]) -> Self {
+            let mut summary = Self::new();
+            for &value in values {
+                summary.push(value);
+            }
+            summary
+        }
+
+        pub fn push(&mut self, value: f64) {
+            self.count += 1;
+            let delta = value - self.mean;
+            self.mean += delta / self.count as f64;
+            self.m2 += delta * (value - self.mean);
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+
+        /// Combines the samples summarized by `other` into `self`, as if
+        /// every sample had been pushed here (Chan et al.'s parallel update)
+        pub fn merge(&mut self, other: &StreamingSummary) {
+            if other.count == 0 {
+                return;
+            }
+            if self.count == 0 {
+                *self = *other;
+                return;
+            }
+            let (a, b) = (self.count as f64, other.count as f64);
+            let total = a + b;
+            let delta = other.mean - self.mean;
+            self.mean += delta * b / total;
+            self.m2 += other.m2 + delta * delta * a * b / total;
+            self.count += other.count;
+            self.min = self.min.min(other.min);
+            self.max = self.max.max(other.max);
+        }
+
+        pub fn count(&self) -> u64 {
+            self.count
+        }
+
+        /// NaN when no samples have been pushed
+        pub fn mean(&self) -> f64 {
+            if self.count == 0 {
+                f64::NAN
+            } else {
+                self.mean
+            }
+        }
+
+        /// Sample variance, NaN for fewer than two samples
+        pub fn variance(&self) -> f64 {
+            if self.count < 2 {
+                f64::NAN
+            } else {
+                self.m2 / (self.count - 1) as f64
+            }
+        }
+
+        pub fn std_dev(&self) -> f64 {
+            self.variance().sqrt()
+        }
+
+        pub fn min(&self) -> f64 {
+            if self.count == 0 {
+                f64::NAN
+            } else {
+                self.min
+            }
+        }
+
+        pub fn max(&self) -> f64 {
+            if self.count == 0 {
+                f64::NAN
+            } else {
+                self.max
+            }
+        }
+    }
+
+    pub struct SimulationStatistics {
+        metrics: HashMap<String, Vec<f64>>,
+        streaming: HashMap<String, StreamingSummary>,
+        seed: Option<u64>,
+    }
+
+    impl SimulationStatistics {
+        pub fn new(metrics: HashMap<String, Vec<f64>>) -> Self {
+            Self {
+                metrics,
+                streaming: HashMap::new(),
+                seed: None,
+            }
+        }
+
+        /// Adds metrics that were only summarized while streaming. They appear
+        /// in `calculate_summary` with a NaN median; analyses that need the
+        /// individual samples don't see them. A metric with both stored
+        /// samples and a streaming summary is summarized over both.
+        pub fn with_streaming(mut self, streaming: HashMap<String, StreamingSummary>) -> Self {
+            self.streaming = streaming;
+            self
+        }
+
+        /// Seeds the resampling done by `mean_confidence_interval` in place of
+        /// the seed passed to it, as engines with a seed do
+        pub fn with_seed(mut self, seed: u64) -> Self {
+            self.seed = Some(seed);
+            self
+        }
+
+        /// Percentile bootstrap interval for the mean of a metric at the given
+        /// `confidence` (e.g. 0.95). Resampling uses the statistics seed when
+        /// one was set and `seed` otherwise, so the interval is reproducible
+        /// either way. `None` for fewer than two samples or invalid parameters.
+        pub fn mean_confidence_interval(
+            &self,
+            metric: &str,
+            confidence: f64,
+            resamples: usize,
+            seed: u64,
+        ) -> Option<(f64, f64)> {
+            let values = self.metrics.get(metric).filter(|values| values.len() >= 2)?;
+            bootstrap_ci(values, kahan_mean, resamples, confidence, self.seed.unwrap_or(seed)).ok()
+        }
+
+        pub fn calculate_summary(&self) -> HashMap<String, MetricSummary> {
+            let mut summaries = self
+                .metrics
+                .iter()
+                .map(|(name, values)| {
+                    let mean = if values.len() > KAHAN_MEAN_THRESHOLD {
+                        kahan_mean(values)
+                    } else {
+                        values.mean()
+                    };
+                    let median = if values.is_empty() {
+                        f64::NAN
+                    } else {
+                        interpolated_percentile(&sorted_samples(values), 50.0)
+                    };
+                    let summary = MetricSummary {
+                        mean,
+                        std_dev: values.std_dev(),
+                        min: values.min(),
+                        max: values.max(),
+                        median,
+                    };
+                    (name.clone(), summary)
+                })
+                .collect::<HashMap<_, _>>();
+
+            for (name, streaming) in &self.streaming {
+                let mut combined = self
+                    .metrics
+                    .get(name)
+                    .map(|values| StreamingSummary::from_samples(values))
+                    .unwrap_or_default();
+                combined.merge(streaming);
+                let summary = MetricSummary {
+                    mean: combined.mean(),
+                    std_dev: combined.std_dev(),
+                    min: combined.min(),
+                    max: combined.max(),
+                    median: f64::NAN,
+                };
+                summaries.insert(name.clone(), summary);
+            }
+            summaries
+        }
+
+        /// Percentile `p` in `[0, 100]` of a metric, linearly interpolating
+        /// between order statistics. `None` for an unknown or empty metric or
+        /// an out-of-range `p`.
+        pub fn percentile(&self, metric: &str, p: f64) -> Option<f64> {
+            if !(0.0..=100.0).contains(&p) {
+                return None;
+            }
+            let values = self.metrics.get(metric).filter(|values| !values.is_empty())?;
+            Some(interpolated_percentile(&sorted_samples(values), p))
+        }
+
+        /// The 25th, 50th and 75th percentiles of a metric
+        pub fn quartiles(&self, metric: &str) -> Option<(f64, f64, f64)> {
+            let values = self.metrics.get(metric).filter(|values| !values.is_empty())?;
+            let sorted = sorted_samples(values);
+            Some((
+                interpolated_percentile(&sorted, 25.0),
+                interpolated_percentile(&sorted, 50.0),
+                interpolated_percentile(&sorted, 75.0),
+            ))
+        }
+
+        /// Histogram over the metric's own range. A constant metric gets a
+        /// unit-wide range centred on its value.
+        pub fn histogram(&self, metric: &str, bins: usize) -> Option<Histogram> {
+            let values = self.metrics.get(metric)?;
+            let (lo, hi) = values
+                .iter()
+                .filter(|value| value.is_finite())
+                .fold(None, |range: Option<(f64, f64)>, &value| match range {
+                    Some((lo, hi)) => Some((lo.min(value), hi.max(value))),
+                    None => Some((value, value)),
+                })?;
+            if lo == hi {
+                return self.histogram_with_range(metric, bins, lo - 0.5, hi + 0.5);
+            }
+            self.histogram_with_range(metric, bins, lo, hi)
+        }
+
+        /// Histogram of `bins` equal-width bins over `[lo, hi]`. Values outside
+        /// the range are counted in the first or last bin; NaN is ignored.
+        pub fn histogram_with_range(
+            &self,
+            metric: &str,
+            bins: usize,
+            lo: f64,
+            hi: f64,
+        ) -> Option<Histogram> {
+            if bins == 0 || !lo.is_finite() || !hi.is_finite() || lo >= hi {
+                return None;
+            }
+            let values = self.metrics.get(metric)?;
+
+            let width = (hi - lo) / bins as f64;
+            let edges = (0..=bins).map(|i| lo + i as f64 * width).collect();
+            let mut counts = vec![0; bins];
+            for &value in values.iter().filter(|value| !value.is_nan()) {
+                let bin = ((value - lo) / width).floor().clamp(0.0, (bins - 1) as f64);
+                counts[bin as usize] += 1;
+            }
+            Some(Histogram { edges, counts })
+        }
+
+        /// Flags samples strictly below the `low_pct` percentile or strictly
+        /// above the `high_pct` percentile of the metric, returning their
+        /// indices and values in recording order
+        pub fn detect_anomalies(
+            &self,
+            metric: &str,
+            low_pct: u8,
+            high_pct: u8,
+        ) -> Result<Vec<(usize, f64)>, anyhow::Error> {
+            if low_pct >= high_pct || high_pct > 100 {
+                anyhow::bail!(
+                    "invalid percentile band: low {} must be below high {} and at most 100",
+                    low_pct,
+                    high_pct
+                );
+            }
+            let values = self
+                .metrics
+                .get(metric)
+                .ok_or_else(|| anyhow::anyhow!("unknown metric '{}'", metric))?;
+            if values.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let sorted = sorted_samples(values);
+            let low = interpolated_percentile(&sorted, low_pct as f64);
+            let high = interpolated_percentile(&sorted, high_pct as f64);
+
+            Ok(values
+                .iter()
+                .copied()
+                .enumerate()
+                .filter(|&(_, value)| value < low || value > high)
+                .collect())
+        }
+
+        /// Pearson's r between two metrics sampled in lockstep. `None` when the
+        /// metrics differ in length, have fewer than two samples, or either
+        /// has zero variance.
+        pub fn correlation(&self, metric_a: &str, metric_b: &str) -> Option<f64> {
+            if self.metrics.get(metric_a)?.len() != self.metrics.get(metric_b)?.len() {
+                return None;
+            }
+            self.running_correlation(metric_a, metric_b)
+        }
+
+        /// Pearson correlation between two metrics computed with a one-pass
+        /// Welford co-moment accumulator. Samples are paired by index; when the
+        /// metrics have different lengths the trailing samples of the longer one
+        /// are ignored. Returns `None` for fewer than two pairs or zero variance.
+        pub fn running_correlation(&self, metric_a: &str, metric_b: &str) -> Option<f64> {
+            let a = self.metrics.get(metric_a)?;
+            let b = self.metrics.get(metric_b)?;
+
+            let mut n = 0.0;
+            let (mut mean_a, mut mean_b) = (0.0, 0.0);
+            let (mut m2_a, mut m2_b, mut co_moment) = (0.0, 0.0, 0.0);
+            for (&x, &y) in a.iter().zip(b.iter()) {
+                n += 1.0;
+                let dx = x - mean_a;
+                mean_a += dx / n;
+                let dy = y - mean_b;
+                mean_b += dy / n;
+                m2_a += dx * (x - mean_a);
+                m2_b += dy * (y - mean_b);
+                co_moment += dx * (y - mean_b);
+            }
+
+            if n < 2.0 || m2_a <= 0.0 || m2_b <= 0.0 {
+                return None;
+            }
+            Some(co_moment / (m2_a * m2_b).sqrt())
+        }
+    }
+
+    /// Low-variance systematic resampling of a weighted particle set: one
+    /// uniform offset in `[0, 1/n)` is drawn and `n` evenly spaced pointers are
+    /// walked through the cumulative normalized weights. Returns the selected
+    /// particle index for each pointer.
+    pub fn systematic_resample(weights: &[f64], seed: u64) -> Result<Vec<usize>, anyhow::Error> {
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            anyhow::bail!("particle weights must be finite and non-negative");
+        }
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            anyhow::bail!("cannot resample particles whose weights are all zero");
+        }
+
+        let n = weights.len();
+        let step = 1.0 / n as f64;
+        let offset = simula_ml::util::seeded_rng(seed).gen_range(0.0..step);
+        let mut indices = Vec::with_capacity(n);
+        let mut cumulative = weights[0] / total;
+        let mut particle = 0;
+        for i in 0..n {
+            let pointer = offset + i as f64 * step;
+            while pointer > cumulative && particle < n - 1 {
+                particle += 1;
+                cumulative += weights[particle] / total;
+            }
+            indices.push(particle);
+        }
+        Ok(indices)
+    }
+
+    /// Percentile bootstrap confidence interval at `level` (e.g. 0.95) for
+    /// `stat`, evaluated on `n_resamples` resamples drawn with replacement.
+    /// The same seed always yields the same interval.
+    pub fn bootstrap_ci(
+        values: &[f64],
+        stat: fn(&[f64]) -> f64,
+        n_resamples: usize,
+        level: f64,
+        seed: u64,
+    ) -> Result<(f64, f64), anyhow::Error> {
+        if values.is_empty() {
+            anyhow::bail!("cannot bootstrap an empty sample");
+        }
+        if n_resamples == 0 {
+            anyhow::bail!("bootstrap needs at least one resample");
+        }
+        if !(level > 0.0 && level < 1.0) {
+            anyhow::bail!("confidence level must be in (0, 1), got {}", level);
+        }
+
+        let mut rng = simula_ml::util::seeded_rng(seed);
+        let mut resample = vec![0.0; values.len()];
+        let mut stats = Vec::with_capacity(n_resamples);
+        for _ in 0..n_resamples {
+            for slot in &mut resample {
+                *slot = values[rng.gen_range(0..values.len())];
+            }
+            stats.push(stat(&resample));
+        }
+
+        let sorted = sorted_samples(&stats);
+        let tail = (1.0 - level) / 2.0 * 100.0;
+        Ok((
+            interpolated_percentile(&sorted, tail),
+            interpolated_percentile(&sorted, 100.0 - tail),
+        ))
+    }
+
+    fn sorted_samples(values: &[f64]) -> Vec<f64> {
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        sorted
+    }
+
+    /// Percentile `p` in `[0, 100]` of non-empty sorted samples, linearly
+    /// interpolating between the neighbouring order statistics
+    fn interpolated_percentile(sorted: &[f64], p: f64) -> f64 {
+        let rank = p / 100.0 * (sorted.len() - 1) as f64;
+        let lower = rank.floor() as usize;
+        let upper = rank.ceil() as usize;
+        sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+    }
+
+    /// Binned counts of a metric; bin `i` spans `edges[i]..edges[i + 1]`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Histogram {
+        pub edges: Vec<f64>,
+        pub counts: Vec<usize>,
+    }
+
+    #[derive(Debug)]
+    pub struct MetricSummary {
+        pub mean: f64,
+        pub std_dev: f64,
+        pub min: f64,
+        pub max: f64,
+        pub median: f64,
+    }
+}
+
+/// Visualization utilities for simulation results
+pub mod visualization {
+    use super::*;
+    use std::path::Path;
+
+    pub struct SimulationVisualizer {
+        metrics: HashMap<String, Vec<f64>>,
+    }
+
+    /// Output format of `SimulationVisualizer::generate_report`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ReportFormat {
+        Markdown,
+        Html,
+    }
+
+    impl SimulationVisualizer {
+        pub fn new(metrics: HashMap<String, Vec<f64>>) -> Self {
+            Self { metrics }
+        }
+
+        /// Renders the metric as a line chart over its sample index to a PNG
+        /// at `path`, with the y-axis scaled to the data range
+        pub fn plot_metric(&self, metric_name: &str, path: &Path) -> Result<(), anyhow::Error> {
+            use plotters::prelude::*;
+
+            let values = self
+                .metrics
+                .get(metric_name)
+                .ok_or_else(|| anyhow::anyhow!("unknown metric '{}'", metric_name))?;
+            let finite = values.iter().copied().filter(|value| value.is_finite());
+            let (mut lo, mut hi) = finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), value| {
+                (lo.min(value), hi.max(value))
+            });
+            if lo > hi {
+                anyhow::bail!("metric '{}' has no finite samples to plot", metric_name);
+            }
+            if lo == hi {
+                lo -= 0.5;
+                hi += 0.5;
+            }
+
+            let root = BitMapBackend::new(path, (800, 600)).into_drawing_area();
+            root.fill(&WHITE)?;
+            let mut chart = ChartBuilder::on(&root)
+                .caption(metric_name, ("sans-serif", 24))
+                .margin(10)
+                .x_label_area_size(40)
+                .y_label_area_size(60)
+                .build_cartesian_2d(0usize..values.len().max(2) - 1, lo..hi)?;
+            chart
+                .configure_mesh()
+                .x_desc("sample")
+                .y_desc(metric_name)
+                .draw()?;
+            chart.draw_series(LineSeries::new(
+                values
+                    .iter()
+                    .copied()
+                    .enumerate()
+                    .filter(|(_, value)| value.is_finite()),
+                &BLUE,
+            ))?;
+            root.present()?;
+            Ok(())
+        }
+
+        /// Writes the metrics to a CSV file at `path` with one column per
+        /// metric, sorted by name. Shorter columns are padded with empty cells.
+        pub fn export_csv(&self, path: &Path) -> Result<(), anyhow::Error> {
+            let mut names: Vec<&String> = self.metrics.keys().collect();
+            names.sort();
+
+            let mut writer = csv::Writer::from_path(path)?;
+            writer.write_record(&names)?;
+            let rows = names.iter().map(|name| self.metrics[*name].len()).max().unwrap_or(0);
+            for row in 0..rows {
+                writer.write_record(names.iter().map(|name| {
+                    self.metrics[*name]
+                        .get(row)
+                        .map(|value| value.to_string())
+                        .unwrap_or_default()
+                }))?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+
+        /// Summarizes every metric (sample count, mean, std dev, min, max and
+        /// median) as a table, one row per metric in name order
+        pub fn generate_report(&self, format: ReportFormat) -> Result<String, anyhow::Error> {
+            let summaries = statistics::SimulationStatistics::new(self.metrics.clone()).calculate_summary();
+            let mut names: Vec<&String> = self.metrics.keys().collect();
+            names.sort();
+
+            let rows = names.into_iter().map(|name| {
+                let summary = &summaries[name];
+                let cells = [
+                    self.metrics[name].len().to_string(),
+                    summary.mean.to_string(),
+                    summary.std_dev.to_string(),
+                    summary.min.to_string(),
+                    summary.max.to_string(),
+                    summary.median.to_string(),
+                ];
+                (name, cells)
+            });
+
+            let mut report = String::new();
+            match format {
+                ReportFormat::Markdown => {
+                    report.push_str("# Simulation Report\n\n");
+                    report.push_str(&format!("| Metric | {} |\n", REPORT_COLUMNS.join(" | ")));
+                    report.push_str(&format!("|---|{}\n", "---|".repeat(REPORT_COLUMNS.len())));
+                    for (name, cells) in rows {
+                        report.push_str(&format!("| {} | {} |\n", name, cells.join(" | ")));
+                    }
+                }
+                ReportFormat::Html => {
+                    report.push_str("<h1>Simulation Report</h1>\n<table>\n<tr><th>Metric</th>");
+                    for column in REPORT_COLUMNS {
+                        report.push_str(&format!("<th>{}</th>", column));
+                    }
+                    report.push_str("</tr>\n");
+                    for (name, cells) in rows {
+                        report.push_str(&format!("<tr><td>{}</td>", html_escape(name)));
+                        for cell in cells {
+                            report.push_str(&format!("<td>{}</td>", cell));
+                        }
+                        report.push_str("</tr>\n");
+                    }
+                    report.push_str("</table>\n");
+                }
+            }
+            Ok(report)
+        }
+
+        /// Renders each metric's latest value and sample count as gauges in
+        /// the Prometheus text exposition format, in metric name order. Names
+        /// are sanitized to `[a-zA-Z_:][a-zA-Z0-9_:]*` and the sample count is
+        /// exported as `<name>_samples`. Metrics without samples are omitted.
+        /// When sanitizing maps a metric onto a name already emitted (e.g.
+        /// `a.b` and `a_b`), the later one in name order gets a `_2`, `_3`, ...
+        /// suffix so every family is declared once.
+        pub fn export_prometheus(&self) -> String {
+            let mut names: Vec<&String> = self.metrics.keys().collect();
+            names.sort();
+
+            let mut output = String::new();
+            let mut emitted: HashSet<String> = HashSet::new();
+            for name in names {
+                let values = &self.metrics[name];
+                let Some(&latest) = values.last() else {
+                    continue;
+                };
+                let base = prometheus_name(name);
+                let mut metric = base.clone();
+                let mut suffix = 1;
+                while emitted.contains(&metric) || emitted.contains(&format!("{}_samples", metric)) {
+                    suffix += 1;
+                    metric = format!("{}_{}", base, suffix);
+                }
+                emitted.insert(format!("{}_samples", metric));
+                emitted.insert(metric.clone());
+                output.push_str(&format!("# TYPE {} gauge\n", metric));
+                output.push_str(&format!("{} {}\n", metric, prometheus_value(latest)));
+                output.push_str(&format!("# TYPE {}_samples gauge\n", metric));
+                output.push_str(&format!("{}_samples {}\n", metric, values.len()));
+            }
+            output
+        }
+    }
+
+    const REPORT_COLUMNS: [&str; 6] = ["Samples", "Mean", "Std Dev", "Min", "Max", "Median"];
+
+    fn html_escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    fn prometheus_name(name: &str) -> String {
+        let mut sanitized: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == ':' { c } else { '_' })
+            .collect();
+        if !sanitized.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == ':') {
+            sanitized.insert(0, '_');
+        }
+        sanitized
+    }
+
+    fn prometheus_value(value: f64) -> String {
+        if value.is_nan() {
+            "NaN".to_string()
+        } else if value.is_infinite() {
+            if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+        } else {
+            value.to_string()
+        }
+    }
+}
+
+/// Memoization of repeated simulation runs
+pub mod cache {
+    use super::*;
+
+    struct CachedRun {
+        model_hash: u64,
+        metrics: HashMap<String, Vec<f64>>,
+    }
+
+    /// Caches run metrics per model and seed, invalidating an entry as soon as
+    /// the model's contents no longer match the ones it was recorded for
+    pub struct SimulationCache {
+        entries: HashMap<(String, u64), CachedRun>,
+    }
+
+    impl SimulationCache {
+        pub fn new() -> Self {
+            Self {
+                entries: HashMap::new(),
+            }
+        }
+
+        pub fn get(&mut self, model: &AIModel, seed: u64) -> Option<&HashMap<String, Vec<f64>>> {
+            let key = (model.name.clone(), seed);
+            let model_hash = model.content_hash();
+            let stale = self
+                .entries
+                .get(&key)
+                .is_some_and(|entry| entry.model_hash != model_hash);
+            if stale {
+                self.entries.remove(&key);
+            }
+            self.entries.get(&key).map(|entry| &entry.metrics)
+        }
+
+        pub fn insert(&mut self, model: &AIModel, seed: u64, metrics: HashMap<String, Vec<f64>>) {
+            self.entries.insert(
+                (model.name.clone(), seed),
+                CachedRun {
+                    model_hash: model.content_hash(),
+                    metrics,
+                },
+            );
+        }
+
+        /// Returns the cached metrics for `model` and `seed`, running the
+        /// simulation only on a miss
+        pub async fn get_or_run<F, Fut>(
+            &mut self,
+            model: &AIModel,
+            seed: u64,
+            run: F,
+        ) -> Result<HashMap<String, Vec<f64>>, anyhow::Error>
+        where
+            F: FnOnce() -> Fut,
+            Fut: Future<Output = Result<HashMap<String, Vec<f64>>, anyhow::Error>>,
+        {
+            if let Some(metrics) = self.get(model, seed) {
+                return Ok(metrics.clone());
+            }
+            let metrics = run().await?;
+            self.insert(model, seed, metrics.clone());
+            Ok(metrics)
+        }
+
+        pub fn len(&self) -> usize {
+            self.entries.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.entries.is_empty()
+        }
+
+        pub fn clear(&mut self) {
+            self.entries.clear();
+        }
+    }
+
+    impl Default for SimulationCache {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simula_ai::ParameterValue;
+
+    fn model(name: &str) -> AIModel {
+        AIModel::new(simula_ai::ModelType::SimulationModel, name.to_string())
+    }
+
+    fn event(time: f64, model_id: &str, event_type: EventType) -> Event {
+        Event::builder().time(time).event_type(event_type).model_id(model_id).build().unwrap()
+    }
+
+    #[test]
+    fn evaluation_records_the_evaluator_accuracy_separately_from_updates() {
+        let schedule = |engine: &mut SimulationEngine, time: f64, model_id: &str, event_type: EventType| {
+            let event = Event::builder().time(time).event_type(event_type).model_id(model_id).build();
+            engine.schedule_event(event.unwrap()).unwrap();
+        };
+        let mut engine = SimulationEngine::with_seed(1);
+        let mut scored = model("scored");
+        scored.add_parameter("w".to_string(), ParameterValue::Vector(vec![3.0, 4.0]), true);
+        engine.add_model(scored);
+        engine.add_model(model("plain"));
+        engine.register_evaluator("scored", Box::new(|model: &AIModel| Ok(1.0 / model.parameter_norm())));
+        schedule(&mut engine, 1.0, "scored", EventType::ModelUpdate);
+        schedule(&mut engine, 2.0, "scored", EventType::Evaluation);
+        schedule(&mut engine, 3.0, "plain", EventType::Evaluation);
+        while engine.step().unwrap().is_some() {}
+
+        let metrics = engine.metrics();
+        assert_eq!(metrics["scored.parameter_norm"], vec![5.0]);
+        assert_eq!(metrics["scored.accuracy"], vec![0.2]);
+        assert_eq!(metrics["plain.evaluations"], vec![1.0]);
+        assert!(!metrics.contains_key("plain.accuracy"));
+
+        // A model re-added under a removed model's name starts without its
+        // evaluator, as does every model after `reset_all`
+        let removed = engine.remove_model("scored").unwrap();
+        engine.add_model(removed.clone());
+        schedule(&mut engine, 4.0, "scored", EventType::Evaluation);
+        while engine.step().unwrap().is_some() {}
+        assert_eq!(engine.metrics()["scored.evaluations"], vec![1.0]);
+
+        engine.register_evaluator("scored", Box::new(|_: &AIModel| Ok(0.5)));
+        engine.reset_all();
+        engine.add_model(removed);
+        schedule(&mut engine, 1.0, "scored", EventType::Evaluation);
+        while engine.step().unwrap().is_some() {}
+        assert_eq!(engine.metrics()["scored.evaluations"], vec![1.0]);
+
+        engine.register_evaluator("scored", Box::new(|_: &AIModel| Ok(1.5)));
+        schedule(&mut engine, 2.0, "scored", EventType::Evaluation);
+        assert!(engine.step().is_err());
+    }
+
+    #[test]
+    fn scenario_file_events_fire_without_priorities() {
+        let json = r#"{
+            "seed": 4,
+            "end_time": 5.0,
+            "models": [{"model_type": "SimulationModel", "name": "m", "parameters": {}}],
+            "events": [
+                {"time": 2.0, "event_type": "TrainingStep", "model_id": "m"},
+                {"time": 1.0, "event_type": "DataArrival", "model_id": "m", "priority": 2},
+                {"time": 8.0, "event_type": "Evaluation", "model_id": "m"}
+            ],
+            "metrics": ["m.accuracy"]
+        }"#;
+        let scenario = Scenario::from_json(json).unwrap();
+        assert_eq!(scenario.events[0].priority, 0);
+
+        let mut engine = SimulationEngine::from_scenario(&scenario).unwrap();
+        engine.enable_trace();
+        engine.run_until(scenario.end_time).unwrap();
+        let fired: Vec<_> = engine.trace().iter().map(|entry| (entry.time, entry.event_type.as_str())).collect();
+        assert_eq!(fired, vec![(1.0, "DataArrival"), (2.0, "TrainingStep")]);
+        assert_eq!(engine.pending_events(), 1);
+        assert!(engine.metrics()["m.accuracy"].is_empty());
+
+        let round_trip = Scenario::from_json(&scenario.to_json().unwrap()).unwrap();
+        assert_eq!(round_trip.events.len(), 3);
+        let unknown = r#"{"end_time": 1.0, "events": [{"time": 0.5, "event_type": "Evaluation", "model_id": "x"}]}"#;
+        assert!(SimulationEngine::from_scenario(&Scenario::from_json(unknown).unwrap()).is_err());
+    }
+
+    #[test]
+    fn trace_records_events_in_firing_order() {
+        let mut engine = SimulationEngine::with_seed(1);
+        engine.add_model(model("a"));
+        engine.add_model(model("b"));
+        engine.set_tie_break(TieBreak::ByPriority);
+        engine.enable_trace();
+        for (time, model_id, priority) in [(2.0, "a", 0), (1.0, "b", 0), (2.0, "b", 5)] {
+            let event = Event::builder()
+                .time(time)
+                .event_type(EventType::DataArrival)
+                .model_id(model_id)
+                .priority(priority)
+                .build();
+            engine.schedule_event(event.unwrap()).unwrap();
+        }
+        while engine.step().unwrap().is_some() {}
+
+        let fired: Vec<_> = engine
+            .trace()
+            .iter()
+            .map(|entry| (entry.time, entry.model_id.as_str(), entry.event_type.as_str()))
+            .collect();
+        assert_eq!(
+            fired,
+            vec![(1.0, "b", "DataArrival"), (2.0, "b", "DataArrival"), (2.0, "a", "DataArrival")]
+        );
+    }
+
+    #[test]
+    fn barrier_holds_later_events_until_earlier_ones_drain() {
+        let run = |barrier: bool| {
+            let mut engine = SimulationEngine::with_seed(1);
+            for name in ["a", "b", "c"] {
+                engine.add_model(model(name));
+            }
+            engine.set_arrival_latency(3.0, 0.0).unwrap();
+            for (time, model_id, event_type) in [
+                (1.0, "a", EventType::DataArrival),
+                (2.2, "c", EventType::Evaluation),
+                (2.5, "b", EventType::Evaluation),
+                (3.0, "a", EventType::Evaluation),
+                (3.5, "c", EventType::Evaluation),
+            ] {
+                let event = Event::builder().time(time).event_type(event_type).model_id(model_id).build();
+                engine.schedule_event(event.unwrap()).unwrap();
+            }
+            if barrier {
+                engine.schedule_barrier(2.0, vec!["a".to_string(), "b".to_string()]).unwrap();
+            }
+            engine.enable_trace();
+            engine.run_until(10.0).unwrap();
+            assert_eq!(engine.pending_events(), 0);
+            engine
+                .trace()
+                .iter()
+                .map(|entry| (entry.time, format!("{}:{}", entry.model_id, entry.event_type)))
+                .collect::<Vec<_>>()
+        };
+        let fired = |expected: &[(f64, &str)]| -> Vec<(f64, String)> {
+            expected.iter().map(|&(time, name)| (time, name.to_string())).collect()
+        };
+
+        // The arrival due at 1.0 lands at 4.0 either way; only the barrier
+        // keeps the listed models' later events behind it
+        assert_eq!(
+            run(false),
+            fired(&[
+                (2.2, "c:Evaluation"),
+                (2.5, "b:Evaluation"),
+                (3.0, "a:Evaluation"),
+                (3.5, "c:Evaluation"),
+                (4.0, "a:DataArrival"),
+            ])
+        );
+        assert_eq!(
+            run(true),
+            fired(&[
+                (2.2, "c:Evaluation"),
+                (3.5, "c:Evaluation"),
+                (4.0, "a:DataArrival"),
+                (4.0, "b:Evaluation"),
+                (4.0, "a:Evaluation"),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn cache_hit_skips_the_run_until_parameters_change() {
+        let mut cache = cache::SimulationCache::new();
+        let mut model = model("cached");
+        let mut runs = 0;
+        let mut run = || {
+            runs += 1;
+            async {
+                let mut metrics = HashMap::new();
+                metrics.insert("reward".to_string(), vec![1.0, 2.0]);
+                Ok(metrics)
+            }
+        };
+
+        let first = cache.get_or_run(&model, 7, &mut run).await.unwrap();
+        let second = cache.get_or_run(&model, 7, &mut run).await.unwrap();
+        assert_eq!(first, second);
+
+        model.add_parameter("w".to_string(), ParameterValue::Scalar(1.0), true);
+        cache.get_or_run(&model, 7, &mut run).await.unwrap();
+        assert_eq!(runs, 2);
+    }
+
+    #[test]
+    fn prometheus_export_keeps_sanitized_names_unique() {
+        let mut metrics = HashMap::new();
+        metrics.insert("a.b".to_string(), vec![1.0, 2.0]);
+        metrics.insert("a_b".to_string(), vec![3.0]);
+        metrics.insert("a_b_samples".to_string(), vec![4.0]);
+        metrics.insert("empty".to_string(), vec![]);
+        let output = visualization::SimulationVisualizer::new(metrics).export_prometheus();
+
+        let mut families = HashSet::new();
+        for line in output.lines() {
+            if let Some(declaration) = line.strip_prefix("# TYPE ") {
+                let (family, kind) = declaration.split_once(' ').unwrap();
+                assert_eq!(kind, "gauge");
+                assert!(families.insert(family.to_string()), "{} declared twice", family);
+            } else {
+                let (family, value) = line.split_once(' ').unwrap();
+                assert!(families.contains(family), "{} has no TYPE line", family);
+                value.parse::<f64>().unwrap();
+            }
+        }
+        assert_eq!(families.len(), 6);
+        assert!(output.contains("a_b 2\n") && output.contains("a_b_2 3\n") && output.contains("a_b_samples_2 4\n"));
+        assert!(visualization::SimulationVisualizer::new(HashMap::new()).export_prometheus().is_empty());
+    }
+
+    #[test]
+    fn streaming_summary_matches_batch_statistics() {
+        let values: Vec<f64> = (0..1000).map(|i| ((i * 37) % 101) as f64 / 7.0 - 3.0).collect();
+        let mut batch = HashMap::new();
+        batch.insert("x".to_string(), values.clone());
+        let expected = statistics::SimulationStatistics::new(batch).calculate_summary().remove("x").unwrap();
+
+        let streaming = statistics::StreamingSummary::from_samples(&values);
+        assert!((streaming.mean() - expected.mean).abs() < 1e-12);
+        assert!((streaming.std_dev() - expected.std_dev).abs() < 1e-12);
+        assert_eq!((streaming.min(), streaming.max()), (expected.min, expected.max));
+
+        // Half the samples stored, half streamed: summarized over both
+        let mut stored = HashMap::new();
+        stored.insert("x".to_string(), values[..400].to_vec());
+        let mut streamed = HashMap::new();
+        streamed.insert("x".to_string(), statistics::StreamingSummary::from_samples(&values[400..]));
+        let merged = statistics::SimulationStatistics::new(stored)
+            .with_streaming(streamed)
+            .calculate_summary()
+            .remove("x")
+            .unwrap();
+        assert!((merged.mean - expected.mean).abs() < 1e-12);
+        assert!((merged.std_dev - expected.std_dev).abs() < 1e-12);
+        assert_eq!((merged.min, merged.max), (expected.min, expected.max));
+    }
+
+    #[tokio::test]
+    async fn antithetic_pairs_reduce_the_variance_of_pair_means() {
+        async fn pair_mean_variance(mut simulation: parallel::ParallelSimulation) -> f64 {
+            for engine in simulation.engines_mut() {
+                engine.add_model(model("m"));
+                engine
+                    .schedule_stochastic(
+                        EventType::DataArrival,
+                        "m".to_string(),
+                        ArrivalDist::Weibull { shape: 1.0, scale: 1.0 },
+                    )
+                    .unwrap();
+            }
+            simulation.run_parallel(5.0).await.unwrap();
+            let arrivals: Vec<f64> = simulation
+                .engines_mut()
+                .iter()
+                .map(|engine| engine.metrics().get("m.data_arrivals").map_or(0, Vec::len) as f64)
+                .collect();
+            let pair_means: Vec<f64> = arrivals.chunks(2).map(|pair| (pair[0] + pair[1]) / 2.0).collect();
+            let mean = pair_means.iter().sum::<f64>() / pair_means.len() as f64;
+            pair_means.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (pair_means.len() - 1) as f64
+        }
+
+        let antithetic = pair_mean_variance(parallel::ParallelSimulation::with_antithetic(300, 9).unwrap()).await;
+        let independent = pair_mean_variance(parallel::ParallelSimulation::with_base_seed(600, 9)).await;
+        assert!(antithetic < 0.8 * independent, "{antithetic} vs {independent}");
+
+        let mut paired = parallel::ParallelSimulation::with_antithetic(2, 40).unwrap();
+        let seeds: Vec<_> = paired.engines_mut().iter().map(SimulationEngine::seed).collect();
+        assert_eq!(seeds, vec![Some(40), Some(40), Some(41), Some(41)]);
+        assert!(parallel::ParallelSimulation::with_antithetic(0, 40).is_err());
+    }
+
+    #[tokio::test]
+    async fn parallel_aggregation_is_deterministic_and_includes_streaming_metrics() {
+        let mut simulation = parallel::ParallelSimulation::with_base_seed(3, 5);
+        for (i, engine) in simulation.engines_mut().iter_mut().enumerate() {
+            engine.record_metric("reward", i as f64);
+            engine.record_metric("loss", 10.0 + i as f64);
+            engine.enable_streaming_metrics();
+            engine.record_metric("latency", i as f64);
+            engine.record_metric("latency", 2.0 * i as f64);
+        }
+        simulation.run_parallel(1.0).await.unwrap();
+
+        let first = simulation.aggregate_results();
+        assert_eq!(first, simulation.aggregate_results());
+        assert_eq!(first["reward"], vec![0.0, 1.0, 2.0]);
+        assert!(!first.contains_key("latency"));
+
+        let streaming = simulation.aggregate_streaming_results();
+        let latency = streaming["latency"];
+        assert_eq!(latency.count(), 6);
+        assert!((latency.mean() - 1.5).abs() < 1e-12);
+        assert_eq!((latency.min(), latency.max()), (0.0, 4.0));
+
+        let summary = simulation.aggregate_statistics().calculate_summary();
+        assert!(summary.contains_key("reward") && summary.contains_key("latency"));
+    }
+
+    #[test]
+    fn exponential_arrivals_average_one_over_rate() {
+        let mut engine = SimulationEngine::with_seed(11);
+        engine.add_model(model("m"));
+        assert!(engine
+            .schedule_stochastic(EventType::DataArrival, "m".to_string(), ArrivalDist::Exponential { rate: 0.0 })
+            .is_err());
+        engine
+            .schedule_stochastic(EventType::DataArrival, "m".to_string(), ArrivalDist::Exponential { rate: 2.0 })
+            .unwrap();
+
+        let arrivals = 5000;
+        let mut last = 0.0;
+        for _ in 0..arrivals {
+            last = engine.step().unwrap().expect("stream keeps itself scheduled");
+        }
+        let mean_gap = last / arrivals as f64;
+        assert!((mean_gap - 0.5).abs() < 0.03, "mean inter-arrival {}", mean_gap);
+    }
+
+    #[tokio::test]
+    async fn panicking_worker_shrinks_the_worker_count() {
+        let mut simulation = parallel::ParallelSimulation::new(3);
+        for (worker, engine) in simulation.engines_mut().iter_mut().enumerate() {
+            engine.add_model(model(&format!("m{}", worker)));
+            engine.register_handler("tick", Box::new(move |_, _, _| if worker == 1 { panic!("worker blew up") } else { Ok(()) }));
+            let event = Event::builder()
+                .time(1.0)
+                .event_type(EventType::Custom("tick".to_string()))
+                .model_id(format!("m{}", worker))
+                .build();
+            engine.schedule_event(event.unwrap()).unwrap();
+        }
+
+        let error = simulation.run_parallel(10.0).await.unwrap_err();
+        assert!(error.to_string().starts_with("worker 1 failed"), "{}", error);
+        assert_eq!(simulation.num_workers(), 2);
+        assert!(simulation.engines_mut()[0].get_model("m0").is_some());
+        assert!(simulation.engines_mut()[1].get_model("m2").is_some());
+    }
+
+    #[tokio::test]
+    async fn handlers_record_metrics_and_undeclared_names_are_flagged() {
+        let mut declaring = model("m");
+        declaring.set_simulation_config(simula_ai::SimulationConfig {
+            time_steps: 10,
+            parallel_simulations: 1,
+            metrics: vec![simula_ai::Metric::Reward, simula_ai::Metric::Custom("latency".to_string())],
+        });
+        assert_eq!(declaring.declared_metrics(), vec!["reward", "latency"]);
+
+        let mut engine = SimulationEngine::new();
+        engine.add_model(declaring);
+        engine.register_handler(
+            "serve",
+            Box::new(|_, time, sink| {
+                sink.record("reward", 1.0);
+                sink.record("latency", time);
+                sink.record("throughput", 2.0);
+                Ok(())
+            }),
+        );
+        for (time, event_type) in [(1.0, EventType::Custom("serve".to_string())), (2.0, EventType::DataArrival)] {
+            let event = Event::builder().time(time).event_type(event_type).model_id("m").build();
+            engine.schedule_event(event.unwrap()).unwrap();
+        }
+        engine.run(10.0).await.unwrap();
+
+        assert_eq!(engine.metrics()["latency"], vec![1.0]);
+        assert_eq!(engine.metrics()["throughput"], vec![2.0]);
+        assert_eq!(engine.metrics()["m.data_arrivals"], vec![1.0]);
+        assert_eq!(engine.undeclared_metrics().iter().collect::<Vec<_>>(), vec!["throughput"]);
+    }
+
+    #[tokio::test]
+    async fn metric_stream_is_bounded_and_the_run_never_blocks() {
+        let mut engine = SimulationEngine::new();
+        engine.add_model(model("m"));
+        let overflow = 10;
+        for i in 0..METRIC_STREAM_CAPACITY + overflow {
+            let event = Event::builder().time(i as f64).event_type(EventType::DataArrival).model_id("m").build();
+            engine.schedule_event(event.unwrap()).unwrap();
+        }
+
+        let (run, mut receiver) = engine.run_with_stream(f64::MAX);
+        run.await.unwrap();
+        let mut streamed = 0;
+        while let Some((name, _)) = receiver.recv().await {
+            assert_eq!(name, "m.data_arrivals");
+            streamed += 1;
+        }
+        assert_eq!(streamed, METRIC_STREAM_CAPACITY);
+        assert_eq!(engine.metrics()["m.data_arrivals"].len(), METRIC_STREAM_CAPACITY + overflow);
+    }
+
+    #[tokio::test]
+    async fn parallel_checkpoint_resumes_to_the_uninterrupted_aggregate() {
+        fn setup() -> parallel::ParallelSimulation {
+            let mut simulation = parallel::ParallelSimulation::with_base_seed(3, 20);
+            for engine in simulation.engines_mut() {
+                engine.add_model(model("m"));
+                engine
+                    .schedule_stochastic(EventType::DataArrival, "m".to_string(), ArrivalDist::Exponential { rate: 2.0 })
+                    .unwrap();
+                engine.set_arrival_latency(0.3, 0.1).unwrap();
+                engine.schedule_recurring(0.5, 1.0, EventType::Evaluation, "m".to_string()).unwrap();
+            }
+            simulation
+        }
+
+        let mut uninterrupted = setup();
+        uninterrupted.run_parallel(30.0).await.unwrap();
+
+        let mut interrupted = setup();
+        interrupted.run_parallel(12.5).await.unwrap();
+        let saved = serde_json::to_string(&interrupted.checkpoint()).unwrap();
+        drop(interrupted);
+        let snapshot: parallel::ParallelSnapshot = serde_json::from_str(&saved).unwrap();
+        assert_eq!(snapshot.base_seed(), Some(20));
+        let mut resumed = parallel::ParallelSimulation::restore(snapshot);
+        resumed.run_parallel(30.0).await.unwrap();
+
+        assert_eq!(resumed.num_workers(), 3);
+        assert_eq!(resumed.aggregate_results_sorted(), uninterrupted.aggregate_results_sorted());
+        let clocks = |simulation: &mut parallel::ParallelSimulation| {
+            simulation.engines_mut().iter().map(|engine| engine.time).collect::<Vec<_>>()
+        };
+        assert_eq!(clocks(&mut resumed), clocks(&mut uninterrupted));
+    }
+
+    #[tokio::test]
+    async fn same_seed_reproduces_the_metrics_of_a_run() {
+        async fn run(seed: u64) -> (f64, BTreeMap<String, Vec<f64>>) {
+            let mut engine = SimulationEngine::with_seed(seed);
+            engine.add_model(model("m"));
+            engine
+                .schedule_stochastic(EventType::DataArrival, "m".to_string(), ArrivalDist::Exponential { rate: 2.0 })
+                .unwrap();
+            engine.set_arrival_latency(0.5, 0.2).unwrap();
+            engine.schedule_recurring(1.0, 1.0, EventType::ModelUpdate, "m".to_string()).unwrap();
+            engine.run(25.0).await.unwrap();
+            (engine.time, engine.metrics().clone().into_iter().collect())
+        }
+
+        let first = run(5).await;
+        assert!(first.1["m.data_arrivals"].len() > 10);
+        assert_eq!(run(5).await, first);
+        assert_ne!(run(6).await, first);
+        assert_eq!(SimulationEngine::with_seed(5).seed(), Some(5));
+        assert_eq!(SimulationEngine::new().seed(), None);
+    }
+
+    #[tokio::test]
+    async fn conditional_events_run_only_while_the_guard_accepts() {
+        fn loss_above(threshold: f64) -> EventGuard {
+            Box::new(move |model: &AIModel| {
+                matches!(model.parameters["loss"].value, ParameterValue::Scalar(loss) if loss > threshold)
+            })
+        }
+        let mut engine = SimulationEngine::new();
+        let mut training = model("m");
+        training.add_parameter("loss".to_string(), ParameterValue::Scalar(0.5), true);
+        engine.add_model(training);
+        engine.register_handler(
+            "converge",
+            Box::new(|model, _, _| {
+                model.add_parameter("loss".to_string(), ParameterValue::Scalar(0.05), true);
+                Ok(())
+            }),
+        );
+
+        engine.schedule_conditional(1.0, EventType::TrainingStep, "m".to_string(), loss_above(0.1)).unwrap();
+        engine.schedule_conditional(2.0, EventType::TrainingStep, "m".to_string(), loss_above(0.9)).unwrap();
+        let event = Event::builder().time(3.0).event_type(EventType::Custom("converge".to_string())).model_id("m");
+        engine.schedule_event(event.build().unwrap()).unwrap();
+        // Guards see the model as it is at fire time, not at scheduling time
+        engine.schedule_conditional(4.0, EventType::TrainingStep, "m".to_string(), loss_above(0.1)).unwrap();
+
+        engine.run_until(2.0).unwrap();
+        assert_eq!(engine.metrics()["m.training_steps"], vec![1.0]);
+        assert_eq!(engine.time, 2.0);
+        engine.run_until(10.0).unwrap();
+        assert_eq!(engine.metrics()["m.training_steps"], vec![1.0]);
+        assert_eq!(engine.time, 4.0);
+        assert!(engine.guards.is_empty());
+    }
+
+    #[test]
+    fn tie_break_policies_order_simultaneous_events() {
+        let firing_order = |policy: Option<TieBreak>| {
+            let mut engine = SimulationEngine::new();
+            for name in ["a", "b", "c"] {
+                engine.add_model(model(name));
+            }
+            if let Some(policy) = policy {
+                engine.set_tie_break(policy);
+            }
+            for (model_id, priority) in [("c", 1), ("a", 0), ("b", 5), ("a", 5)] {
+                let event = Event::builder()
+                    .time(1.0)
+                    .event_type(EventType::Evaluation)
+                    .model_id(model_id)
+                    .priority(priority)
+                    .build();
+                engine.schedule_event(event.unwrap()).unwrap();
+            }
+            engine.enable_trace();
+            engine.run_until(2.0).unwrap();
+            engine.trace().iter().map(|entry| entry.model_id.as_str()).collect::<String>()
+        };
+
+        assert_eq!(firing_order(None), "caba");
+        assert_eq!(firing_order(Some(TieBreak::Fifo)), "caba");
+        // Equal keys fall back to scheduling order under either policy
+        assert_eq!(firing_order(Some(TieBreak::ByPriority)), "baca");
+        assert_eq!(firing_order(Some(TieBreak::ByModelId)), "aabc");
+    }
+
+    #[test]
+    fn event_builder_rejects_invalid_fields() {
+        let valid = || Event::builder().time(1.0).event_type(EventType::Evaluation).model_id("m");
+        let rejected = [
+            Event::builder().event_type(EventType::Evaluation).model_id("m"),
+            valid().time(-1.0),
+            valid().time(f64::NAN),
+            valid().time(f64::INFINITY),
+            Event::builder().time(1.0).model_id("m"),
+            Event::builder().time(1.0).event_type(EventType::Evaluation),
+            valid().model_id(""),
+            valid().deadline(0.5),
+            valid().deadline(f64::NAN),
+        ];
+        for builder in rejected {
+            let description = format!("{:?}", builder);
+            assert!(matches!(builder.build(), Err(RuntimeError::Event(_))), "{} was accepted", description);
+        }
+
+        let event = valid().priority(3).deadline(2.0).build().unwrap();
+        assert_eq!((event.time, event.model_id.as_str(), event.priority, event.deadline), (1.0, "m", 3, Some(2.0)));
+        assert_eq!(valid().build().unwrap().priority, 0);
+
+        // Built priorities order simultaneous events under `ByPriority`
+        let mut engine = SimulationEngine::new();
+        engine.add_model(model("m"));
+        engine.set_tie_break(TieBreak::ByPriority);
+        engine.enable_trace();
+        engine.schedule_event(valid().build().unwrap()).unwrap();
+        engine.schedule_event(valid().event_type(EventType::ModelUpdate).priority(1).build().unwrap()).unwrap();
+        engine.run_until(1.0).unwrap();
+        let fired: Vec<&str> = engine.trace().iter().map(|entry| entry.event_type.as_str()).collect();
+        assert_eq!(fired, vec!["ModelUpdate", "Evaluation"]);
+    }
+
+    #[test]
+    fn anomaly_detection_flags_exactly_the_injected_outliers() {
+        let mut values: Vec<f64> = (0..100).map(|i| 10.0 + (i % 7) as f64 * 0.1).collect();
+        values[13] = 100.0;
+        values[57] = -50.0;
+        values[88] = 250.0;
+        let mut metrics = HashMap::new();
+        metrics.insert("latency".to_string(), values);
+        metrics.insert("empty".to_string(), Vec::new());
+        let stats = statistics::SimulationStatistics::new(metrics);
+
+        assert_eq!(
+            stats.detect_anomalies("latency", 1, 97).unwrap(),
+            vec![(13, 100.0), (57, -50.0), (88, 250.0)]
+        );
+        assert!(stats.detect_anomalies("empty", 1, 99).unwrap().is_empty());
+        assert!(stats.detect_anomalies("latency", 50, 10).is_err());
+        assert!(stats.detect_anomalies("latency", 10, 10).is_err());
+        assert!(stats.detect_anomalies("latency", 1, 101).is_err());
+        assert!(stats.detect_anomalies("missing", 1, 99).is_err());
+    }
+
+    #[derive(Default)]
+    struct Seen {
+        events: Vec<(f64, String)>,
+        metrics: Vec<String>,
+        summaries: Vec<(f64, usize, usize)>,
+    }
+
+    struct CountingObserver(std::sync::Arc<std::sync::Mutex<Seen>>);
+
+    impl Observer for CountingObserver {
+        fn on_event(&mut self, event: &Event, time: f64) {
+            self.0.lock().unwrap().events.push((time, event.model_id.clone()));
+        }
+
+        fn on_metric(&mut self, name: &str, _value: f64) {
+            self.0.lock().unwrap().metrics.push(name.to_string());
+        }
+
+        fn on_run_end(&mut self, summary: &RunSummary) {
+            let summary = (summary.end_time, summary.events_processed, summary.pending_events);
+            self.0.lock().unwrap().summaries.push(summary);
+        }
+    }
+
+    #[tokio::test]
+    async fn observers_see_every_event_metric_and_run_end() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Seen::default()));
+        let mut engine = SimulationEngine::new();
+        engine.add_model(model("m"));
+        engine.add_observer(Box::new(CountingObserver(seen.clone())));
+        engine.add_observer(Box::new(CountingObserver(seen.clone())));
+        for time in [1.0, 2.0, 3.0, 20.0] {
+            let event = Event::builder().time(time).event_type(EventType::Evaluation).model_id("m").build();
+            engine.schedule_event(event.unwrap()).unwrap();
+        }
+        engine.run(10.0).await.unwrap();
+
+        // Both observers receive each callback, one after the other
+        let seen = seen.lock().unwrap();
+        let times: Vec<f64> = seen.events.iter().map(|(time, _)| *time).collect();
+        assert_eq!(times, vec![1.0, 1.0, 2.0, 2.0, 3.0, 3.0]);
+        assert!(seen.events.iter().all(|(_, model_id)| model_id == "m"));
+        assert_eq!(seen.metrics, vec!["m.evaluations"; 6]);
+        assert_eq!(seen.summaries, vec![(3.0, 3, 1); 2]);
+    }
+
+    #[tokio::test]
+    async fn recurring_events_repeat_until_cancelled() {
+        let mut engine = SimulationEngine::new();
+        engine.add_model(model("m"));
+        for period in [0.0, -1.0, f64::NAN] {
+            assert!(engine.schedule_recurring(1.0, period, EventType::Evaluation, "m".to_string()).is_err());
+        }
+        let id = engine.schedule_recurring(1.0, 2.0, EventType::Evaluation, "m".to_string()).unwrap();
+        engine.enable_trace();
+
+        engine.run(10.0).await.unwrap();
+        let times: Vec<f64> = engine.trace().iter().map(|entry| entry.time).collect();
+        assert_eq!(times, vec![1.0, 3.0, 5.0, 7.0, 9.0]);
+        assert_eq!(engine.pending_events(), 1);
+
+        assert!(engine.cancel(id));
+        assert!(!engine.cancel(id));
+        engine.run(20.0).await.unwrap();
+        assert_eq!(engine.trace().len(), 5);
+        assert_eq!(engine.pending_events(), 0);
+    }
+
+    #[test]
+    fn mean_confidence_interval_covers_the_true_mean_and_honours_its_seed() {
+        let mut rng = ChaCha8Rng::seed_from_u64(42);
+        let normal = Normal::new(10.0, 2.0).unwrap();
+        let stats_for = |values: Vec<f64>| {
+            let mut metrics = HashMap::new();
+            metrics.insert("x".to_string(), values);
+            statistics::SimulationStatistics::new(metrics)
+        };
+
+        let mut covered = 0;
+        for trial in 0..100 {
+            let stats = stats_for((0..40).map(|_| normal.sample(&mut rng)).collect());
+            let (low, high) = stats.mean_confidence_interval("x", 0.9, 200, trial).unwrap();
+            assert_eq!(stats.mean_confidence_interval("x", 0.9, 200, trial), Some((low, high)));
+            if low <= 10.0 && 10.0 <= high {
+                covered += 1;
+            }
+        }
+        assert!((80..=98).contains(&covered), "90% interval covered the mean {} times in 100", covered);
+
+        let stats = stats_for((0..40).map(|_| normal.sample(&mut rng)).collect());
+        let first = stats.mean_confidence_interval("x", 0.9, 500, 1);
+        assert_ne!(stats.mean_confidence_interval("x", 0.9, 500, 2), first);
+        // A statistics seed, as set by a seeded engine, takes precedence
+        let seeded = stats.with_seed(1);
+        assert_eq!(seeded.mean_confidence_interval("x", 0.9, 500, 2), first);
+
+        assert_eq!(stats_for(vec![1.0]).mean_confidence_interval("x", 0.9, 100, 0), None);
+        assert_eq!(stats_for(vec![1.0, 2.0]).mean_confidence_interval("x", 1.5, 100, 0), None);
+    }
+
+    #[tokio::test]
+    async fn cleared_model_events_never_fire_but_the_model_stays() {
+        let mut engine = SimulationEngine::with_seed(1);
+        engine.add_model(model("quiet"));
+        engine.add_model(model("busy"));
+        for time in [1.0, 2.0, 3.0] {
+            engine.schedule_event(event(time, "quiet", EventType::Evaluation)).unwrap();
+        }
+        engine.schedule_event(event(2.0, "busy", EventType::Evaluation)).unwrap();
+        engine.record_metric("quiet.reward", 1.0);
+
+        assert_eq!(engine.clear_model_events("quiet"), 3);
+        assert_eq!(engine.clear_model_events("quiet"), 0);
+        assert_eq!(engine.pending_events(), 1);
+
+        engine.run(10.0).await.unwrap();
+        assert!(engine.get_model("quiet").is_some());
+        assert!(!engine.metrics().contains_key("quiet.evaluations"));
+        assert_eq!(engine.metrics()["quiet.reward"], vec![1.0]);
+        assert_eq!(engine.metrics()["busy.evaluations"], vec![1.0]);
+    }
+
+    #[test]
+    fn systematic_resampling_keeps_particles_in_proportion_to_weight() {
+        let weights = [0.1, 5.0, 0.1, 0.1];
+        let indices = statistics::systematic_resample(&weights, 3).unwrap();
+        assert_eq!(indices, statistics::systematic_resample(&weights, 3).unwrap());
+        assert!(indices.iter().filter(|&&i| i == 1).count() >= 3, "{:?}", indices);
+
+        // The first half carries three quarters of the total weight
+        let weights: Vec<f64> = (0..1000).map(|i| if i < 500 { 3.0 } else { 1.0 }).collect();
+        let indices = statistics::systematic_resample(&weights, 7).unwrap();
+        let heavy = indices.iter().filter(|&&i| i < 500).count();
+        assert!((749..=751).contains(&heavy), "{}", heavy);
+
+        assert!(statistics::systematic_resample(&[0.0, 0.0], 1).is_err());
+        assert!(statistics::systematic_resample(&[1.0, -1.0], 1).is_err());
+    }
+
+    #[test]
+    fn advancing_jumps_to_the_next_event_without_processing_it() {
+        let mut engine = SimulationEngine::with_seed(1);
+        engine.add_model(model("m"));
+        assert_eq!(engine.advance_to_next_event(), None);
+        engine.schedule_event(event(7.0, "m", EventType::Evaluation)).unwrap();
+        engine.schedule_event(event(5.0, "m", EventType::Evaluation)).unwrap();
+
+        assert_eq!(engine.advance_to_next_event(), Some(5.0));
+        assert_eq!(engine.advance_to_next_event(), Some(5.0));
+        assert_eq!(engine.pending_events(), 2);
+        assert!(engine.metrics().is_empty());
+
+        assert_eq!(engine.step().unwrap(), Some(5.0));
+        assert_eq!(engine.advance_to_next_event(), Some(7.0));
+        assert_eq!(engine.step().unwrap(), Some(7.0));
+        assert_eq!(engine.metrics()["m.evaluations"].len(), 2);
+    }
+
+    #[tokio::test]
+    async fn sub_simulation_runs_on_its_trigger_and_lifts_child_metrics() {
+        let mut child = SimulationEngine::with_seed(2);
+        child.add_model(model("c"));
+        for time in 1..=5 {
+            child.schedule_event(event(time as f64, "c", EventType::Evaluation)).unwrap();
+        }
+        let sub = SubSimulation::new(child, EventType::TrainingStep, 3.0).lift_metric("c.evaluations");
+
+        let mut parent = SimulationEngine::with_seed(1);
+        parent.add_model(model("p"));
+        parent.register_sub_simulation("p", sub).unwrap();
+        parent.schedule_event(event(1.0, "p", EventType::Evaluation)).unwrap();
+        parent.run(2.0).await.unwrap();
+        assert!(!parent.metrics().contains_key("p.c.evaluations"));
+
+        parent.schedule_event(event(3.0, "p", EventType::TrainingStep)).unwrap();
+        parent.run(4.0).await.unwrap();
+        assert_eq!(parent.metrics()["p.c.evaluations"].len(), 3);
+        parent.schedule_event(event(5.0, "p", EventType::TrainingStep)).unwrap();
+        parent.run(6.0).await.unwrap();
+        assert_eq!(parent.metrics()["p.c.evaluations"].len(), 5);
+        assert_eq!(parent.sub_simulation("p").unwrap().engine().time, 6.0);
+
+        // Nesting one level past the limit is refused at registration
+        let mut nested = SimulationEngine::new();
+        for _ in 0..MAX_SUB_SIMULATION_DEPTH {
+            let mut outer = SimulationEngine::new();
+            outer.register_sub_simulation("m", SubSimulation::new(nested, EventType::Evaluation, 1.0)).unwrap();
+            nested = outer;
+        }
+        let too_deep = SubSimulation::new(nested, EventType::Evaluation, 1.0);
+        assert!(SimulationEngine::new().register_sub_simulation("m", too_deep).is_err());
+    }
+
+    #[test]
+    fn kahan_mean_beats_naive_summation_on_long_runs() {
+        let mut values = vec![1.0e8];
+        values.resize(1_000_001, 0.1);
+        let exact = (1.0e8 + 0.1 * 1_000_000.0) / 1_000_001.0;
+        let naive = values.iter().sum::<f64>() / values.len() as f64;
+        let compensated = statistics::kahan_mean(&values);
+        assert!((compensated - exact).abs() < 1e-9, "{}", compensated);
+        assert!((naive - exact).abs() > (compensated - exact).abs());
+        assert!(statistics::kahan_mean(&[]).is_nan());
+
+        // Summaries of long metrics use the compensated mean
+        let mut metrics = HashMap::new();
+        metrics.insert("long".to_string(), values);
+        let summary = statistics::SimulationStatistics::new(metrics).calculate_summary();
+        assert_eq!(summary["long"].mean, compensated);
+    }
+
+    #[tokio::test]
+    async fn event_costs_occupy_the_engine_and_delay_later_events() {
+        let mut engine = SimulationEngine::with_seed(1);
+        engine.add_model(model("m"));
+        engine.set_event_cost(EventTypeKind::Evaluation, 2.0).unwrap();
+        for cost in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(engine.set_event_cost(EventTypeKind::Custom, cost).is_err());
+        }
+        engine.register_handler("free", Box::new(|_, _, _| Ok(())));
+        for time in [1.0, 2.0, 3.0] {
+            engine.schedule_event(event(time, "m", EventType::Evaluation)).unwrap();
+        }
+        engine.schedule_event(event(3.5, "m", EventType::Custom("free".to_string()))).unwrap();
+        engine.enable_trace();
+        let snapshot = engine.checkpoint();
+
+        // Each evaluation busies the engine for 2 time units, so the queue
+        // drains at 3, 5 and 7 and the free custom event runs at 7
+        engine.run(10.0).await.unwrap();
+        let times: Vec<f64> = engine.trace().iter().map(|entry| entry.time).collect();
+        assert_eq!(times, vec![1.0, 3.0, 5.0, 7.0]);
+        assert_eq!(engine.time, 7.0);
+
+        let mut restored = SimulationEngine::restore(snapshot);
+        restored.register_handler("free", Box::new(|_, _, _| Ok(())));
+        restored.run(10.0).await.unwrap();
+        assert_eq!(restored.time, 7.0);
+    }
+
+    #[test]
+    fn bootstrap_interval_brackets_the_mean_and_is_seeded() {
+        let values: Vec<f64> = (0..50).map(|i| ((i * 37) % 11) as f64).collect();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let (low, high) = statistics::bootstrap_ci(&values, statistics::kahan_mean, 2000, 0.95, 9).unwrap();
+        assert!(low < mean && mean < high, "({}, {}) around {}", low, high, mean);
+        assert_eq!(
+            statistics::bootstrap_ci(&values, statistics::kahan_mean, 2000, 0.95, 9).unwrap(),
+            (low, high)
+        );
+
+        assert!(statistics::bootstrap_ci(&[], statistics::kahan_mean, 10, 0.9, 1).is_err());
+        assert!(statistics::bootstrap_ci(&values, statistics::kahan_mean, 0, 0.9, 1).is_err());
+        assert!(statistics::bootstrap_ci(&values, statistics::kahan_mean, 10, 1.0, 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn reset_clears_the_run_but_reset_all_clears_the_setup() {
+        let mut engine = SimulationEngine::with_seed(1);
+        engine.add_model(model("m"));
+        engine.set_event_cost(EventTypeKind::Evaluation, 1.0).unwrap();
+        engine.schedule_event(event(2.0, "m", EventType::Evaluation)).unwrap();
+        engine.schedule_event(event(9.0, "m", EventType::Evaluation)).unwrap();
+        engine.record_metric("x", 1.0);
+        engine.run(5.0).await.unwrap();
+
+        engine.reset();
+        assert_eq!(engine.time, 0.0);
+        assert_eq!(engine.pending_events(), 0);
+        assert!(engine.metrics().is_empty());
+
+        // The model and its event cost survive a plain reset
+        engine.schedule_event(event(1.0, "m", EventType::Evaluation)).unwrap();
+        engine.run(5.0).await.unwrap();
+        assert_eq!(engine.time, 2.0);
+        assert_eq!(engine.metrics()["m.evaluations"], vec![1.0]);
+
+        engine.reset_all();
+        assert!(engine.get_model("m").is_none());
+        assert!(engine.event_costs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn arrival_latency_delays_only_data_arrivals() {
+        let mut engine = SimulationEngine::with_seed(4);
+        engine.add_model(model("m"));
+        assert!(engine.set_arrival_latency(-1.0, 0.0).is_err());
+        assert!(engine.set_arrival_latency(1.0, f64::NAN).is_err());
+        engine.set_arrival_latency(2.0, 0.0).unwrap();
+        engine.schedule_event(event(1.0, "m", EventType::DataArrival)).unwrap();
+        engine.schedule_event(event(1.5, "m", EventType::Evaluation)).unwrap();
+        engine.enable_trace();
+        engine.run(10.0).await.unwrap();
+        let fired: Vec<(f64, &str)> = engine
+            .trace()
+            .iter()
+            .map(|entry| (entry.time, entry.event_type.as_str()))
+            .collect();
+        assert_eq!(fired, vec![(1.5, "Evaluation"), (3.0, "DataArrival")]);
+
+        // Jitter spreads the delays around the mean
+        let mut engine = SimulationEngine::with_seed(4);
+        engine.add_model(model("m"));
+        engine.set_arrival_latency(2.0, 0.5).unwrap();
+        for i in 0..200 {
+            engine.schedule_event(event(i as f64 * 10.0, "m", EventType::DataArrival)).unwrap();
+        }
+        engine.enable_trace();
+        engine.run(3000.0).await.unwrap();
+        let delays: Vec<f64> =
+            engine.trace().iter().map(|entry| entry.time - (entry.time / 10.0).floor() * 10.0).collect();
+        let mean = delays.iter().sum::<f64>() / delays.len() as f64;
+        assert_eq!(delays.len(), 200);
+        assert!((mean - 2.0).abs() < 0.15, "{}", mean);
+        assert!(delays.iter().any(|&d| (d - 2.0).abs() > 0.1));
+
+        engine.set_arrival_latency(0.0, 0.0).unwrap();
+        assert!(engine.arrival_latency.is_none());
+    }
+
+    #[test]
+    fn event_kinds_ignore_the_custom_payload() {
+        let mut counts: HashMap<EventTypeKind, usize> = HashMap::new();
+        for event_type in [
+            EventType::Custom("a".to_string()),
+            EventType::Custom("b".to_string()),
+            EventType::Evaluation,
+            EventType::Barrier(vec!["m".to_string()]),
+        ] {
+            *counts.entry((&event_type).into()).or_default() += 1;
+        }
+        assert_eq!(counts[&EventTypeKind::Custom], 2);
+        assert_eq!(counts[&EventTypeKind::Evaluation], 1);
+        assert_eq!(counts[&EventTypeKind::Barrier], 1);
+        assert_eq!(EventTypeKind::from(&EventType::DataArrival), EventTypeKind::DataArrival);
+    }
+
+    #[tokio::test]
+    async fn deactivated_models_skip_events_but_keep_their_metrics() {
+        let mut engine = SimulationEngine::with_seed(1);
+        engine.add_model(model("a"));
+        engine.add_model(model("b"));
+        engine.record_metric("b.reward", 3.0);
+        engine.schedule_event(event(1.0, "b", EventType::Evaluation)).unwrap();
+        engine.schedule_event(event(2.0, "a", EventType::Evaluation)).unwrap();
+        engine.deactivate_model("b").unwrap();
+        assert!(!engine.is_active("b") && engine.is_active("a"));
+        assert!(engine.deactivate_model("missing").is_err());
+        assert!(engine.reactivate_model("missing").is_err());
+
+        engine.run(5.0).await.unwrap();
+        assert!(engine.get_model("b").is_some());
+        assert!(!engine.metrics().contains_key("b.evaluations"));
+        assert_eq!(engine.metrics()["a.evaluations"], vec![1.0]);
+        let summary = engine.statistics().calculate_summary();
+        assert_eq!(summary["b.reward"].mean, 3.0);
+
+        engine.reactivate_model("b").unwrap();
+        engine.schedule_event(event(6.0, "b", EventType::Evaluation)).unwrap();
+        engine.run(10.0).await.unwrap();
+        assert_eq!(engine.metrics()["b.evaluations"], vec![1.0]);
+    }
+
+    #[tokio::test]
+    async fn profiling_attributes_time_to_event_kinds_and_handlers() {
+        let mut engine = SimulationEngine::with_seed(1);
+        engine.add_model(model("m"));
+        engine.register_handler(
+            "slow",
+            Box::new(|_, _, _| {
+                std::thread::sleep(Duration::from_millis(10));
+                Ok(())
+            }),
+        );
+        engine.schedule_event(event(1.0, "m", EventType::Custom("slow".to_string()))).unwrap();
+        engine.run(2.0).await.unwrap();
+        assert!(engine.profile_report().is_empty());
+
+        engine.enable_profiling();
+        engine.schedule_event(event(3.0, "m", EventType::Evaluation)).unwrap();
+        engine.schedule_event(event(3.0, "m", EventType::Custom("slow".to_string()))).unwrap();
+        engine.run(5.0).await.unwrap();
+        let report = engine.profile_report();
+        assert_eq!(report.len(), 2, "{:?}", report);
+        assert!(report["Custom(slow)"] >= Duration::from_millis(10));
+        assert!(report["Custom(slow)"] > report["Evaluation"] * 10, "{:?}", report);
+    }
+
+    #[tokio::test]
+    async fn past_events_are_rejected_or_clamped_by_policy() {
+        let mut engine = SimulationEngine::with_seed(1);
+        engine.add_model(model("m"));
+        engine.schedule_event(event(5.0, "m", EventType::Evaluation)).unwrap();
+        engine.run(10.0).await.unwrap();
+
+        let error = engine.schedule_event(event(3.0, "m", EventType::Evaluation)).unwrap_err();
+        assert!(matches!(error, RuntimeError::Time(_)));
+        let message = error.to_string();
+        assert!(message.contains("time 3") && message.contains("current time 5"), "{}", message);
+        assert!(engine.schedule_recurring(3.0, 1.0, EventType::Evaluation, "m".to_string()).is_err());
+        assert_eq!(engine.pending_events(), 0);
+
+        engine.set_past_event_policy(PastEventPolicy::ClampToNow);
+        engine.schedule_event(event(3.0, "m", EventType::Evaluation)).unwrap();
+        engine.enable_trace();
+        engine.run(10.0).await.unwrap();
+        let times: Vec<f64> = engine.trace().iter().map(|entry| entry.time).collect();
+        assert_eq!(times, vec![5.0]);
+    }
+
+    #[tokio::test]
+    async fn warmup_drops_transient_samples_but_still_runs_their_events() {
+        async fn run(warmup: f64) -> SimulationEngine {
+            let mut engine = SimulationEngine::with_seed(1);
+            engine.add_model(model("m"));
+            engine.set_warmup(warmup).unwrap();
+            engine.register_handler(
+                "latency",
+                Box::new(|_, time, sink| {
+                    sink.record("latency", if time < 5.0 { 100.0 } else { 1.0 });
+                    Ok(())
+                }),
+            );
+            engine.schedule_recurring(1.0, 1.0, EventType::Custom("latency".to_string()), "m".to_string()).unwrap();
+            engine.enable_trace();
+            engine.run(10.0).await.unwrap();
+            engine
+        }
+
+        let cold = run(0.0).await;
+        let warmed = run(5.0).await;
+        assert_eq!(warmed.trace().len(), cold.trace().len());
+        let cold_summary = cold.statistics().calculate_summary();
+        let warmed_summary = warmed.statistics().calculate_summary();
+        assert_eq!(cold.metrics()["latency"].len(), 10);
+        assert!(cold_summary["latency"].mean > 40.0);
+        assert_eq!(warmed.metrics()["latency"], vec![1.0; 6]);
+        assert_eq!(warmed_summary["latency"].mean, 1.0);
+
+        let mut engine = SimulationEngine::new();
+        assert!(engine.set_warmup(-1.0).is_err());
+        assert!(engine.set_warmup(f64::INFINITY).is_err());
+    }
+
+    #[tokio::test]
+    async fn non_finite_and_negative_event_times_are_rejected_without_panicking() {
+        let mut engine = SimulationEngine::with_seed(1);
+        engine.add_model(model("m"));
+        engine.schedule_event(event(2.0, "m", EventType::Evaluation)).unwrap();
+        for time in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -1.0] {
+            let bad = Event { time, ..event(1.0, "m", EventType::Evaluation) };
+            assert!(matches!(engine.schedule_event(bad), Err(RuntimeError::Time(_))), "{}", time);
+        }
+        // Non-finite times are rejected even when past events would be clamped
+        engine.set_past_event_policy(PastEventPolicy::ClampToNow);
+        let nan = Event { time: f64::NAN, ..event(1.0, "m", EventType::Evaluation) };
+        assert!(engine.schedule_event(nan).is_err());
+
+        assert_eq!(engine.pending_events(), 1);
+        engine.run(5.0).await.unwrap();
+        assert_eq!(engine.metrics()["m.evaluations"], vec![1.0]);
+    }
+
+    #[test]
+    fn correlation_pairs_metrics_sampled_in_lockstep() {
+        let mut metrics = HashMap::new();
+        metrics.insert("a".to_string(), vec![1.0, 2.0, 3.0, 4.0]);
+        metrics.insert("b".to_string(), vec![3.0, 5.0, 7.0, 9.0]);
+        metrics.insert("inverse".to_string(), vec![4.0, 3.0, 2.0, 1.0]);
+        metrics.insert("flat".to_string(), vec![1.0, 1.0, 1.0, 1.0]);
+        metrics.insert("short".to_string(), vec![1.0, 2.0]);
+        let stats = statistics::SimulationStatistics::new(metrics);
+
+        assert!((stats.correlation("a", "b").unwrap() - 1.0).abs() < 1e-12);
+        assert!((stats.correlation("a", "inverse").unwrap() + 1.0).abs() < 1e-12);
+        assert_eq!(stats.correlation("a", "flat"), None);
+        assert_eq!(stats.correlation("a", "short"), None);
+        assert_eq!(stats.correlation("a", "missing"), None);
+    }
+
+    #[test]
+    fn reports_list_sorted_metric_summaries_in_markdown_and_html() {
+        let mut metrics = HashMap::new();
+        metrics.insert("loss".to_string(), vec![1.0, 2.0]);
+        metrics.insert("acc<x>".to_string(), vec![0.25]);
+        let visualizer = visualization::SimulationVisualizer::new(metrics);
+
+        let markdown = visualizer.generate_report(visualization::ReportFormat::Markdown).unwrap();
+        assert!(markdown.starts_with("# Simulation Report\n"));
+        assert!(markdown.contains("| loss | 2 | 1.5 |"), "{}", markdown);
+        assert!(markdown.find("acc<x>").unwrap() < markdown.find("loss").unwrap());
+
+        let html = visualizer.generate_report(visualization::ReportFormat::Html).unwrap();
+        assert!(html.contains("<td>loss</td><td>2</td><td>1.5</td>"), "{}", html);
+        assert!(html.contains("<td>acc&lt;x&gt;</td>"));
+        assert!(!html.contains("acc<x>"));
+    }
+
+    #[tokio::test]
+    async fn removed_models_drop_their_pending_events() {
+        let mut engine = SimulationEngine::with_seed(1);
+        engine.add_model(model("gone"));
+        engine.add_model(model("kept"));
+        engine.schedule_event(event(1.0, "gone", EventType::Evaluation)).unwrap();
+        engine.schedule_recurring(2.0, 1.0, EventType::TrainingStep, "gone".to_string()).unwrap();
+        engine.schedule_event(event(3.0, "kept", EventType::Evaluation)).unwrap();
+        engine.run(1.5).await.unwrap();
+        assert_eq!(engine.get_model("gone").unwrap().name, "gone");
+
+        let removed = engine.remove_model("gone").unwrap();
+        assert_eq!(removed.name, "gone");
+        assert!(engine.get_model("gone").is_none());
+        assert!(engine.remove_model("gone").is_none());
+        assert_eq!(engine.pending_events(), 1);
+
+        engine.enable_trace();
+        engine.run(10.0).await.unwrap();
+        let fired: Vec<&str> = engine.trace().iter().map(|entry| entry.model_id.as_str()).collect();
+        assert_eq!(fired, vec!["kept"]);
+        assert_eq!(engine.metrics()["gone.evaluations"], vec![1.0]);
+    }
+}
@@ -1,11 +1,3 @@
-pub mod frontend_tests;
-pub mod ir_tests;
-pub mod backend_tests;
-pub mod runtime_tests;
-pub mod verification_tests;
-pub mod integration_tests;
-pub mod benchmarks;
-
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -0,0 +1,133 @@
+use crate::VerificationError;
+use serde::{Deserialize, Serialize};
+
+/// A single metric sample recorded during a simulation run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceSample {
+    pub time: f64,
+    pub metric: String,
+    pub value: f64,
+}
+
+/// The recorded metrics of a completed simulation run, in recording order
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Trace {
+    pub samples: Vec<TraceSample>,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, time: f64, metric: &str, value: f64) {
+        self.samples.push(TraceSample {
+            time,
+            metric: metric.to_string(),
+            value,
+        });
+    }
+}
+
+/// A named predicate that every sample of a metric must satisfy
+pub struct Invariant {
+    pub name: String,
+    pub metric: String,
+    predicate: Box<dyn Fn(f64) -> bool + Send + Sync>,
+}
+
+impl Invariant {
+    pub fn new(
+        name: &str,
+        metric: &str,
+        predicate: impl Fn(f64) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            metric: metric.to_string(),
+            predicate: Box::new(predicate),
+        }
+    }
+
+    /// Requires every sample of `metric` to lie within `[min, max]`
+    pub fn bounded(name: &str, metric: &str, min: f64, max: f64) -> Self {
+        Self::new(name, metric, move |value| value >= min && value <= max)
+    }
+
+    pub fn holds(&self, value: f64) -> bool {
+        (self.predicate)(value)
+    }
+}
+
+/// A collection of invariants checked together against a trace
+#[derive(Default)]
+pub struct InvariantSet {
+    invariants: Vec<Invariant>,
+}
+
+impl InvariantSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, invariant: Invariant) {
+        self.invariants.push(invariant);
+    }
+
+    pub fn len(&self) -> usize {
+        self.invariants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.invariants.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Invariant> {
+        self.invariants.iter()
+    }
+}
+
+/// Verifies a completed run against every invariant, returning all violations
+/// in trace order rather than stopping at the first one
+pub fn check_trace(trace: &Trace, invariants: &InvariantSet) -> Vec<VerificationError> {
+    let mut violations = Vec::new();
+
+    for sample in &trace.samples {
+        for invariant in invariants.iter().filter(|inv| inv.metric == sample.metric) {
+            if !invariant.holds(sample.value) {
+                violations.push(VerificationError::InvariantViolation(format!(
+                    "{}: {} = {} at time {}",
+                    invariant.name, sample.metric, sample.value, sample.time
+                )));
+            }
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_trace_reports_every_violation() {
+        let mut trace = Trace::new();
+        trace.record(0.0, "loss", 0.5);
+        trace.record(1.0, "loss", -1.0);
+        trace.record(2.0, "accuracy", 0.9);
+        trace.record(3.0, "accuracy", 1.5);
+
+        let mut invariants = InvariantSet::new();
+        invariants.add(Invariant::bounded("non-negative loss", "loss", 0.0, f64::INFINITY));
+        invariants.add(Invariant::bounded("probability", "accuracy", 0.0, 1.0));
+
+        let violations: Vec<String> = check_trace(&trace, &invariants)
+            .iter()
+            .map(|violation| violation.to_string())
+            .collect();
+        assert_eq!(violations.len(), 2);
+        assert!(violations[0].contains("loss = -1 at time 1"));
+        assert!(violations[1].contains("accuracy = 1.5 at time 3"));
+    }
+}
@@ -1,7 +1,5 @@
 pub mod properties;
 pub mod invariants;
-pub mod testing;
-pub mod model;
 
 use thiserror::Error;
 
@@ -0,0 +1,72 @@
+use crate::VerificationError;
+use serde::{Deserialize, Serialize};
+use simula_runtime::resource::ResourceTrace;
+
+/// Properties checked against the usage a runtime `ResourcePool` records
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Property {
+    /// Units in use of `resource` never exceed `max`
+    ResourceBounded { resource: String, max: usize },
+    /// No request for `resource` waits longer than `limit`
+    MaxWait { resource: String, limit: f64 },
+}
+
+impl Property {
+    /// Returns every violation in the trace, each naming its time and value
+    pub fn check(&self, trace: &ResourceTrace) -> Vec<VerificationError> {
+        match self {
+            Property::ResourceBounded { resource, max } => trace
+                .for_resource(resource)
+                .filter(|usage| usage.in_use > *max)
+                .map(|usage| {
+                    VerificationError::PropertyViolation(format!(
+                        "{} has {} units in use at time {}, exceeding the bound of {}",
+                        resource, usage.in_use, usage.time, max
+                    ))
+                })
+                .collect(),
+            Property::MaxWait { resource, limit } => trace
+                .for_resource(resource)
+                .filter_map(|usage| usage.waited.map(|waited| (usage.time, waited)))
+                .filter(|&(_, waited)| waited > *limit)
+                .map(|(time, waited)| {
+                    VerificationError::PropertyViolation(format!(
+                        "request for {} acquired at time {} waited {}, exceeding the limit of {}",
+                        resource, time, waited, limit
+                    ))
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simula_runtime::resource::ResourcePool;
+
+    #[test]
+    fn oversubscribed_pool_trips_bound_and_wait_limit() {
+        let mut pool = ResourcePool::new("gpu", 3);
+        for time in [0.0, 1.0, 2.0, 3.0] {
+            pool.acquire(time);
+        }
+        assert_eq!(pool.waiting(), 1);
+        pool.release(6.0).unwrap();
+
+        let bound = Property::ResourceBounded { resource: "gpu".to_string(), max: 2 };
+        let violations = bound.check(pool.trace());
+        // in use reaches 3 at time 2 and again when the queued request is
+        // granted at time 6
+        assert_eq!(violations.len(), 2);
+        assert!(violations[0].to_string().contains("3 units in use at time 2"));
+
+        let wait = Property::MaxWait { resource: "gpu".to_string(), limit: 2.0 };
+        let violations = wait.check(pool.trace());
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].to_string().contains("acquired at time 6 waited 3"));
+
+        let capacity = Property::ResourceBounded { resource: "gpu".to_string(), max: pool.capacity() };
+        assert!(capacity.check(pool.trace()).is_empty());
+    }
+}